@@ -18,20 +18,30 @@ fn cool_heatmap(heatmap: &mut [u8; 256]) {
     }
 }
 
-fn render_frame<T: io::Write>(
+fn render_frame(
     gfx: &mut Canvas<Color, FrameBuffer>,
     heatmap: &[u8; 256],
-    stream: &mut T,
+    colormap: &Colormap,
+    sink: &mut dyn FrameSink,
 ) -> io::Result<()> {
     let mut x = CELL_GAP;
     let mut y = CELL_GAP;
 
     for i in 0..256 {
         let byteval = heatmap[i];
-        let color = Color::rgb(byteval, 0, 0);
+        let color = colormap.sample(byteval as f64 / 255.0);
         gfx.set_fill(color);
         gfx.fill_rect(x, y, CELL_SIZE, CELL_SIZE);
 
+        // Label each cell with its byte, in whichever of black or white
+        // stands out more against the cell
+        gfx.set_fill(if byteval < 128 {
+            Color::white()
+        } else {
+            Color::black()
+        });
+        gfx.draw_text(x + CELL_SIZE / 2, y + CELL_SIZE / 2, &format!("{:02X}", i));
+
         if (i + 1) % 16 == 0 {
             x = CELL_GAP;
             y += CELL_SIZE + CELL_GAP;
@@ -40,20 +50,24 @@ fn render_frame<T: io::Write>(
         }
     }
 
-    gfx.buffer().write(stream)
+    sink.write_frame(gfx.buffer())
 }
 
 fn main() {
     let background = Color::white();
     let dimension = (CELL_GAP + CELL_SIZE) * 16 + CELL_GAP;
-    let mut stdout = io::stdout();
+    let mut sink = sink_from_args().unwrap();
     let mut stdin = io::stdin();
 
     let buffer = FrameBuffer::new(dimension as u32, dimension as u32);
     let mut gfx = Canvas::new(buffer, background, Color::black());
 
+    gfx.set_text_align(TextAlign::Center);
+    gfx.set_text_baseline(TextBaseline::Middle);
+
+    let colormap = Colormap::inferno();
     let mut heatmap: [u8; 256] = [0; 256];
-    render_frame(&mut gfx, &heatmap, &mut stdout).unwrap();
+    render_frame(&mut gfx, &heatmap, &colormap, sink.as_mut()).unwrap();
     gfx.fill();
 
     let mut byteval: [u8; 1] = [0; 1];
@@ -67,11 +81,13 @@ fn main() {
             heatmap[byteval[0] as usize] += HEATING;
         }
 
-        render_frame(&mut gfx, &heatmap, &mut stdout).unwrap();
+        render_frame(&mut gfx, &heatmap, &colormap, sink.as_mut()).unwrap();
 
         gfx.set_fill(background);
         gfx.fill();
 
         cool_heatmap(&mut heatmap);
     }
+
+    sink.finish().unwrap();
 }
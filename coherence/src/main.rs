@@ -3,7 +3,6 @@ extern crate bitflags;
 
 use framebuffer::*;
 use random;
-use std::io;
 use std::time::{Duration, SystemTime};
 
 const CANVAS_SIZE: i64 = 400;
@@ -226,18 +225,21 @@ impl Grid {
 }
 
 fn main() {
-    let mut stdout = io::stdout();
+    let mut sink = sink_from_args().unwrap();
+    let frames = frame_limit_from_args().unwrap().unwrap_or(2000);
     let buffer = FrameBuffer::new(CANVAS_SIZE as u32, CANVAS_SIZE as u32);
     let mut gfx = Canvas::new(buffer, Color::white(), Color::black());
 
     let mut rng = new_rng();
     let mut grid = Grid::new(&mut rng);
 
-    for _ in 0..2000 {
+    for _ in 0..frames {
         gfx.fill();
         grid.step();
         grid.draw(&mut gfx);
 
-        gfx.buffer().write(&mut stdout).unwrap();
+        sink.write_frame(gfx.buffer()).unwrap();
     }
+
+    sink.finish().unwrap();
 }
@@ -0,0 +1,396 @@
+use crate::polygon::{scan_lines, FillRule};
+use crate::wide::segment_pixels;
+use crate::{Canvas, Color, GraphicBuffer, Path};
+
+/// The number of lines that each row of pixels is split into when working out
+/// how much of each pixel a shape covers
+const SUBLINES: usize = 16;
+
+/// Returns the fractional part of the value
+fn fpart(value: f64) -> f64 {
+    value - value.floor()
+}
+
+/// Returns one minus the fractional part of the value
+fn rfpart(value: f64) -> f64 {
+    1.0 - fpart(value)
+}
+
+impl<Buffer: GraphicBuffer<Color>> Canvas<Color, Buffer> {
+    /// Draws a color with its alpha scaled by the fraction of the pixel that
+    /// the shape covers, relying on the buffer's alpha blending to mix it with
+    /// what's already there
+    pub(crate) fn put_coverage(&mut self, x: i64, y: i64, color: Color, coverage: f64) {
+        if coverage <= 0.0 {
+            return;
+        }
+
+        let alpha = (color.alpha as f64 * coverage.min(1.0)).round() as u8;
+        self.put_point(x, y, Color::rgba(color.r, color.g, color.b, alpha));
+    }
+
+    /// Fills the given closed contours with anti-aliased edges, using the
+    /// given color(x, y) for each pixel
+    pub(crate) fn aa_fill_contours<F>(
+        &mut self,
+        contours: &[Vec<(f64, f64)>],
+        rule: FillRule,
+        color: F,
+    ) where
+        F: Fn(i64, i64) -> Color,
+    {
+        /*
+        Each row is cut into several horizontal lines, and the stretches of
+        each line inside the shape are found the same way as fill_polygon
+        does for pixel centers. Those stretches are exact along the line, so
+        a pixel's coverage is the total length of the stretches that cross it
+        divided by the number of lines. The pixels of a row are only drawn
+        once all of its lines are done.
+         */
+        let (mut left, mut right) = (f64::INFINITY, f64::NEG_INFINITY);
+        for &(x, _) in contours.iter().flatten() {
+            left = left.min(x);
+            right = right.max(x);
+        }
+        if left > right {
+            return;
+        }
+
+        let first = (left + 0.5).floor() as i64;
+        let last = (right + 0.5).floor() as i64;
+        let mut coverage = vec![0.0; (last - first + 1) as usize];
+        let mut current_row = None;
+        let share = 1.0 / SUBLINES as f64;
+
+        let finish_row = |canvas: &mut Self, py: i64, coverage: &mut Vec<f64>| {
+            for (i, amount) in coverage.iter_mut().enumerate() {
+                if *amount > 0.0 {
+                    let px = first + i as i64;
+                    canvas.put_coverage(px, py, color(px, py), *amount);
+                }
+                *amount = 0.0;
+            }
+        };
+
+        scan_lines(contours, rule, SUBLINES, |py, _, start, end| {
+            if current_row != Some(py) {
+                if let Some(row) = current_row {
+                    finish_row(self, row, &mut coverage);
+                }
+                current_row = Some(py);
+            }
+
+            // Shift over so that pixel px covers [px, px + 1) instead of
+            // being centered on px
+            let (start, end) = (start + 0.5, end + 0.5);
+            let (start_px, end_px) = (start.floor() as i64, end.floor() as i64);
+            let index = |px: i64| (px - first) as usize;
+            if start_px == end_px {
+                coverage[index(start_px)] += (end - start) * share;
+                return;
+            }
+
+            coverage[index(start_px)] += (start_px as f64 + 1.0 - start) * share;
+            for px in (start_px + 1)..end_px {
+                coverage[index(px)] += share;
+            }
+            if end_px <= last {
+                coverage[index(end_px)] += (end - end_px as f64) * share;
+            }
+        });
+
+        if let Some(row) = current_row {
+            finish_row(self, row, &mut coverage);
+        }
+    }
+
+    /// Draws an anti-aliased line between the two points using the given
+    /// gradient(ratio) for the color of each pixel
+    fn aa_line<F>(&mut self, x: f64, y: f64, x2: f64, y2: f64, gradient: F)
+    where
+        F: Fn(f64) -> Color,
+    {
+        /*
+        Ref: Xiaolin Wu, "An Efficient Antialiasing Technique", SIGGRAPH '91
+
+        This works like Bresenham, stepping one pixel at a time along the
+        major axis, except that instead of picking the single closest pixel
+        on the minor axis it colors the two pixels that straddle the line.
+        Each gets a share of the color proportional to how close the line
+        passes to its center. The endpoints are weighted again by how much of
+        their pixel the line actually covers along the major axis.
+
+        Integer coordinates are pixel centers, so this lines up with the
+        output of stroke_line when given whole numbers.
+
+        Wu's method only works for lines a single pixel wide. Wider ones are
+        treated as rectangles instead, with each pixel covered according to
+        how far inside the edges of the rectangle it is.
+
+        Under a transform the line is drawn as a path instead, and the ratio
+        comes from projecting each pixel back onto the original line.
+         */
+        if self.is_transformed() {
+            let (dx, dy) = (x2 - x, y2 - y);
+            let length_squared = dx * dx + dy * dy;
+
+            let mut path = Path::new();
+            path.move_to(x, y);
+            path.line_to(x2, y2);
+            self.aa_stroke_path_with(&path, self.line_width, |px, py| {
+                let ratio = if length_squared == 0.0 {
+                    0.0
+                } else {
+                    ((px - x) * dx + (py - y) * dy) / length_squared
+                };
+                gradient(ratio.clamp(0.0, 1.0))
+            });
+            return;
+        } else if self.line_width != 1.0 {
+            let half_width = self.line_width / 2.0;
+            segment_pixels(
+                x,
+                y,
+                x2,
+                y2,
+                half_width,
+                0.5,
+                |px, py, ratio, along, across| {
+                    let coverage =
+                        (0.5 - along).min(1.0) * (half_width + 0.5 - across.abs()).min(1.0);
+                    if coverage > 0.0 {
+                        self.put_coverage(px, py, gradient(ratio), coverage);
+                    }
+                },
+            );
+            return;
+        }
+
+        let steep = (y2 - y).abs() > (x2 - x).abs();
+        let (mut x0, mut y0, mut x1, mut y1) = if steep {
+            (y, x, y2, x2)
+        } else {
+            (x, y, x2, y2)
+        };
+
+        let reversed = x0 > x1;
+        if reversed {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+        let slope = if dx == 0.0 { 1.0 } else { dy / dx };
+
+        let ratio = |px: f64| {
+            let ratio = if dx == 0.0 { 0.0 } else { (px - x0) / dx };
+            if reversed {
+                1.0 - ratio
+            } else {
+                ratio
+            }
+        };
+
+        let plot = |canvas: &mut Self, major: i64, minor: i64, coverage: f64| {
+            let color = gradient(ratio(major as f64).clamp(0.0, 1.0));
+            if steep {
+                canvas.put_coverage(minor, major, color, coverage);
+            } else {
+                canvas.put_coverage(major, minor, color, coverage);
+            }
+        };
+
+        let xend = (x0 + 0.5).floor();
+        let yend = y0 + slope * (xend - x0);
+        let xgap = rfpart(x0 + 0.5);
+        let xstart = xend as i64;
+        plot(self, xstart, yend.floor() as i64, rfpart(yend) * xgap);
+        plot(self, xstart, yend.floor() as i64 + 1, fpart(yend) * xgap);
+        let mut intery = yend + slope;
+
+        let xend = (x1 + 0.5).floor();
+        let yend = y1 + slope * (xend - x1);
+        let xgap = fpart(x1 + 0.5);
+        let xstop = xend as i64;
+        if xstop != xstart {
+            plot(self, xstop, yend.floor() as i64, rfpart(yend) * xgap);
+            plot(self, xstop, yend.floor() as i64 + 1, fpart(yend) * xgap);
+        }
+
+        for px in (xstart + 1)..xstop {
+            plot(self, px, intery.floor() as i64, rfpart(intery));
+            plot(self, px, intery.floor() as i64 + 1, fpart(intery));
+            intery += slope;
+        }
+    }
+
+    /// Draws an anti-aliased line between the two points using the current
+    /// stroke color
+    pub fn aa_stroke_line(&mut self, x: f64, y: f64, x2: f64, y2: f64) {
+        let stroke = self.stroke;
+        self.aa_line(x, y, x2, y2, |_| stroke);
+    }
+
+    /// Draws an anti-aliased line between the two points using the current
+    /// fill color
+    pub fn aa_fill_line(&mut self, x: f64, y: f64, x2: f64, y2: f64) {
+        let fill = self.fill;
+        self.aa_line(x, y, x2, y2, |_| fill);
+    }
+
+    /// Draws an anti-aliased line between the two points using the given
+    /// gradient(ratio)
+    pub fn aa_gstroke_line<F>(&mut self, x: f64, y: f64, x2: f64, y2: f64, gradient: F)
+    where
+        F: Fn(f64) -> Color,
+    {
+        self.aa_line(x, y, x2, y2, gradient);
+    }
+
+    /// Draws an anti-aliased ellipse using the given gradient(angle, radius)
+    /// for the color of each pixel. If stroke is set only a line of the current
+    /// width along the perimeter is drawn, otherwise the whole ellipse is
+    /// filled.
+    fn aa_ellipse<F>(&mut self, x: f64, y: f64, rx: f64, ry: f64, stroke: bool, gradient: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        /*
+        The ellipse is the zero level of:
+
+        f(px, py) = (px / rx)^2 + (py / ry)^2 - 1
+
+        Dividing f by the length of its gradient gives a first order estimate
+        of the signed distance from the pixel center to the perimeter, which
+        is exact for circles and close enough for ellipses that aren't
+        extremely flat:
+
+        |grad f| = 2 * sqrt((px / rx^2)^2 + (py / ry^2)^2)
+
+        A pixel is treated as a unit-wide box, so a fill covers it completely
+        once its center is half a pixel inside the perimeter and not at all
+        once it's half a pixel outside. A stroke is a band centered on the
+        perimeter whose edges are treated the same way.
+
+        None of that holds once a transform has been applied, so in that case
+        the ellipse is drawn as a path instead.
+         */
+        if rx <= 0.0 || ry <= 0.0 {
+            return;
+        }
+
+        if self.is_transformed() {
+            let mut path = Path::new();
+            path.ellipse(x, y, rx, ry, 0.0);
+
+            let color = |px: f64, py: f64| {
+                let (relx, rely) = (px - x, py - y);
+                let scaled = ((relx / rx).powi(2) + (rely / ry).powi(2)).sqrt();
+                gradient(rely.atan2(relx), scaled.min(1.0))
+            };
+
+            if stroke {
+                self.aa_stroke_path_with(&path, self.line_width, color);
+            } else {
+                self.aa_fill_path_with(&path, color);
+            }
+            return;
+        }
+
+        let half_width = if stroke { self.line_width / 2.0 } else { 0.0 };
+        let left = (x - rx - half_width - 1.0).floor() as i64;
+        let right = (x + rx + half_width + 1.0).ceil() as i64;
+        let top = (y - ry - half_width - 1.0).floor() as i64;
+        let bottom = (y + ry + half_width + 1.0).ceil() as i64;
+
+        for py in top..=bottom {
+            let rely = py as f64 - y;
+            for px in left..=right {
+                let relx = px as f64 - x;
+
+                let scaled = ((relx / rx).powi(2) + (rely / ry).powi(2)).sqrt();
+                let slope = 2.0 * ((relx / (rx * rx)).powi(2) + (rely / (ry * ry)).powi(2)).sqrt();
+                let distance = if slope == 0.0 {
+                    f64::NEG_INFINITY
+                } else {
+                    (scaled * scaled - 1.0) / slope
+                };
+
+                let coverage = if stroke {
+                    half_width + 0.5 - distance.abs()
+                } else {
+                    0.5 - distance
+                };
+
+                if coverage > 0.0 {
+                    let color = gradient(rely.atan2(relx), scaled.min(1.0));
+                    self.put_coverage(px, py, color, coverage);
+                }
+            }
+        }
+    }
+
+    /// Draws an anti-aliased circle's perimeter around the given point using
+    /// the current stroke color
+    pub fn aa_stroke_circle(&mut self, x: f64, y: f64, r: f64) {
+        let stroke = self.stroke;
+        self.aa_ellipse(x, y, r, r, true, |_, _| stroke);
+    }
+
+    /// Draws an anti-aliased circle's perimeter around the given point using
+    /// the given gradient(angle)
+    pub fn aa_gstroke_circle<F>(&mut self, x: f64, y: f64, r: f64, gradient: F)
+    where
+        F: Fn(f64) -> Color,
+    {
+        self.aa_ellipse(x, y, r, r, true, |angle, _| gradient(angle));
+    }
+
+    /// Fills an anti-aliased circle around the given point
+    pub fn aa_fill_circle(&mut self, x: f64, y: f64, r: f64) {
+        let fill = self.fill;
+        self.aa_ellipse(x, y, r, r, false, |_, _| fill);
+    }
+
+    /// Fills an anti-aliased circle around the given point using the given
+    /// gradient(angle, radius)
+    pub fn aa_gfill_circle<F>(&mut self, x: f64, y: f64, r: f64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        self.aa_ellipse(x, y, r, r, false, gradient);
+    }
+
+    /// Draws an anti-aliased ellipse's perimeter around the given point, with
+    /// the given horizontal and vertical radii, using the current stroke color
+    pub fn aa_stroke_ellipse(&mut self, x: f64, y: f64, rx: f64, ry: f64) {
+        let stroke = self.stroke;
+        self.aa_ellipse(x, y, rx, ry, true, |_, _| stroke);
+    }
+
+    /// Draws an anti-aliased ellipse's perimeter around the given point using
+    /// the given gradient(angle)
+    pub fn aa_gstroke_ellipse<F>(&mut self, x: f64, y: f64, rx: f64, ry: f64, gradient: F)
+    where
+        F: Fn(f64) -> Color,
+    {
+        self.aa_ellipse(x, y, rx, ry, true, |angle, _| gradient(angle));
+    }
+
+    /// Fills an anti-aliased ellipse around the given point
+    pub fn aa_fill_ellipse(&mut self, x: f64, y: f64, rx: f64, ry: f64) {
+        let fill = self.fill;
+        self.aa_ellipse(x, y, rx, ry, false, |_, _| fill);
+    }
+
+    /// Fills an anti-aliased ellipse around the given point using the given
+    /// gradient(angle, radius), where the radius is scaled so that it's 1 on
+    /// the perimeter
+    pub fn aa_gfill_ellipse<F>(&mut self, x: f64, y: f64, rx: f64, ry: f64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        self.aa_ellipse(x, y, rx, ry, false, gradient);
+    }
+}
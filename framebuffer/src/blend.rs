@@ -0,0 +1,127 @@
+/// Decides how a color that's drawn is combined with the color already in the
+/// buffer. Every mode is mixed in by the drawn color's alpha, so a half
+/// transparent color has half of the effect.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    /// The drawn color covers up what's there
+    Normal,
+    /// The colors are added together, which brightens the buffer like light
+    Additive,
+    /// The colors are multiplied, which darkens the buffer like a filter
+    Multiply,
+    /// The inverse colors are multiplied, which brightens the buffer without
+    /// washing it out as quickly as Additive
+    Screen,
+    /// Dark parts of the buffer are multiplied and light parts are screened,
+    /// which adds contrast
+    Overlay,
+    /// The difference between the colors, which inverts the buffer under
+    /// bright colors and leaves it alone under black
+    Difference,
+    /// The brighter of the two colors, channel by channel
+    Lighten,
+    /// The darker of the two colors, channel by channel
+    Darken,
+}
+
+impl BlendMode {
+    /// Combines a single channel of the color in the buffer with the same
+    /// channel of the drawn color, ignoring alpha
+    pub fn apply(self, base: u8, top: u8) -> u8 {
+        let (base16, top16) = (base as u16, top as u16);
+        match self {
+            BlendMode::Normal => top,
+            BlendMode::Additive => (base16 + top16).min(255) as u8,
+            BlendMode::Multiply => (base16 * top16 / 255) as u8,
+            BlendMode::Screen => (255 - (255 - base16) * (255 - top16) / 255) as u8,
+            BlendMode::Overlay => {
+                if base < 128 {
+                    (2 * base16 * top16 / 255) as u8
+                } else {
+                    (255 - 2 * (255 - base16) * (255 - top16) / 255) as u8
+                }
+            }
+            BlendMode::Difference => base.abs_diff(top),
+            BlendMode::Lighten => base.max(top),
+            BlendMode::Darken => base.min(top),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Canvas, Color, FrameBuffer};
+
+    /// Each mode with what it gives for drawing (40, 120, 240) over (200,
+    /// 100, 30), fully opaque and then at an alpha of 128
+    const EXPECTED: [(BlendMode, [u8; 3], [u8; 3]); 8] = [
+        (BlendMode::Normal, [40, 120, 240], [119, 110, 135]),
+        (BlendMode::Additive, [240, 220, 255], [220, 160, 142]),
+        (BlendMode::Multiply, [31, 47, 28], [115, 73, 28]),
+        (BlendMode::Screen, [209, 173, 242], [204, 136, 136]),
+        (BlendMode::Overlay, [163, 94, 56], [181, 96, 43]),
+        (BlendMode::Difference, [160, 20, 210], [179, 59, 120]),
+        (BlendMode::Lighten, [200, 120, 240], [200, 110, 135]),
+        (BlendMode::Darken, [40, 100, 30], [119, 100, 30]),
+    ];
+
+    const BASE: [u8; 3] = [200, 100, 30];
+    const TOP: [u8; 3] = [40, 120, 240];
+
+    /// Gets the red, green and blue channels of the color
+    fn channels(color: Color) -> [u8; 3] {
+        [color.r, color.g, color.b]
+    }
+
+    #[test]
+    fn modes_on_channels() {
+        for &(mode, full, _) in EXPECTED.iter() {
+            for channel in 0..3 {
+                let result = mode.apply(BASE[channel], TOP[channel]);
+                assert_eq!(result, full[channel], "{:?} channel {}", mode, channel);
+            }
+        }
+    }
+
+    #[test]
+    fn modes_mixed_by_alpha() {
+        let base = Color::rgb(BASE[0], BASE[1], BASE[2]);
+        for &(mode, full, half) in EXPECTED.iter() {
+            let opaque = base.blend_with(Color::rgba(TOP[0], TOP[1], TOP[2], 255), mode);
+            assert_eq!(channels(opaque), full, "{:?}", mode);
+            assert_eq!(opaque.alpha, 255);
+
+            let translucent = base.blend_with(Color::rgba(TOP[0], TOP[1], TOP[2], 128), mode);
+            assert_eq!(channels(translucent), half, "{:?}", mode);
+
+            let clear = base.blend_with(Color::rgba(TOP[0], TOP[1], TOP[2], 0), mode);
+            assert_eq!(channels(clear), BASE, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn canvas_uses_the_mode() {
+        for &(mode, _, half) in EXPECTED.iter() {
+            let base = Color::rgb(BASE[0], BASE[1], BASE[2]);
+            let mut canvas = Canvas::new(FrameBuffer::new(2, 1), base, base);
+            canvas.fill();
+
+            canvas.set_blend_mode(mode);
+            canvas.set_fill(Color::rgba(TOP[0], TOP[1], TOP[2], 128));
+            canvas.fill_point(0, 0);
+            assert_eq!(
+                channels(canvas.get_point(0, 0).unwrap()),
+                half,
+                "{:?}",
+                mode
+            );
+            assert_eq!(
+                channels(canvas.get_point(1, 0).unwrap()),
+                BASE,
+                "{:?}",
+                mode
+            );
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{Canvas, GraphicBuffer};
+
+/// Decides which ways an image is mirrored when it's copied
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flip {
+    None,
+    /// Mirrored left to right
+    Horizontal,
+    /// Mirrored top to bottom
+    Vertical,
+    /// Mirrored both ways, which is the same as turning it halfway around
+    Both,
+}
+
+impl Flip {
+    /// Checks whether the image is mirrored horizontally and vertically
+    fn axes(self) -> (bool, bool) {
+        match self {
+            Flip::None => (false, false),
+            Flip::Horizontal => (true, false),
+            Flip::Vertical => (false, true),
+            Flip::Both => (true, true),
+        }
+    }
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Copies the whole of the source buffer onto the canvas, with its top
+    /// left corner at the given point
+    pub fn blit<Source>(&mut self, source: &Source, x: i64, y: i64)
+    where
+        Source: GraphicBuffer<Element>,
+    {
+        let region = (0, 0, source.width() as i64, source.height() as i64);
+        self.blit_region(source, region, x, y, Flip::None);
+    }
+
+    /// Copies the (x, y, width, height) region of the source buffer onto the
+    /// canvas, with its top left corner at the given point, mirroring it
+    /// according to the flip. The pixels are drawn like any others, so
+    /// transparent ones are blended with what's there and the current
+    /// transform, clip region and blend mode all apply.
+    pub fn blit_region<Source>(
+        &mut self,
+        source: &Source,
+        region: (i64, i64, i64, i64),
+        x: i64,
+        y: i64,
+        flip: Flip,
+    ) where
+        Source: GraphicBuffer<Element>,
+    {
+        let (left, top, width, height) = region;
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let (flip_x, flip_y) = flip.axes();
+        let pixel = |column: i64, row: i64| {
+            let column = if flip_x { width - 1 - column } else { column };
+            let row = if flip_y { height - 1 - row } else { row };
+            source.get_point(left + column, top + row)
+        };
+
+        if !self.is_transformed() {
+            for row in 0..height {
+                for column in 0..width {
+                    if let Some(color) = pixel(column, row) {
+                        self.put_point(x + column, y + row, color);
+                    }
+                }
+            }
+            return;
+        }
+
+        /*
+        Under a transform the copied pixels don't line up with the canvas's
+        pixels any more. Instead, every canvas pixel that the region could
+        cover is moved back through the transform, and takes the color of
+        whichever source pixel it lands on.
+         */
+        let inverse = match self.transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let (x, y) = (x as f64, y as f64);
+        let corners = [
+            (x - 0.5, y - 0.5),
+            (x + width as f64 - 0.5, y - 0.5),
+            (x - 0.5, y + height as f64 - 0.5),
+            (x + width as f64 - 0.5, y + height as f64 - 0.5),
+        ];
+
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in corners {
+            let (cx, cy) = self.transform.apply(corner);
+            min_x = min_x.min(cx);
+            min_y = min_y.min(cy);
+            max_x = max_x.max(cx);
+            max_y = max_y.max(cy);
+        }
+
+        for py in (min_y.floor() as i64)..=(max_y.ceil() as i64) {
+            for px in (min_x.floor() as i64)..=(max_x.ceil() as i64) {
+                let (ux, uy) = inverse.apply((px as f64, py as f64));
+                let column = (ux - x + 0.5).floor() as i64;
+                let row = (uy - y + 0.5).floor() as i64;
+                if column < 0 || column >= width || row < 0 || row >= height {
+                    continue;
+                }
+
+                if let Some(color) = pixel(column, row) {
+                    self.put_point(px, py, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StencilBuffer;
+
+    /// Builds a 5x4 source where every pixel has its own value, 10 * y + x + 1
+    fn source() -> StencilBuffer {
+        let mut source = StencilBuffer::new(5, 4);
+        for y in 0..4 {
+            for x in 0..5 {
+                source.put_point(x, y, (10 * y + x + 1) as u8);
+            }
+        }
+        source
+    }
+
+    /// Creates a 10x8 canvas filled with 99, so that untouched pixels stand
+    /// out from copied ones
+    fn new_canvas() -> Canvas<u8, StencilBuffer> {
+        let mut canvas = Canvas::new(StencilBuffer::new(10, 8), 99, 99);
+        canvas.fill();
+        canvas
+    }
+
+    /// Gets the value at each pixel, row by row
+    fn values(canvas: &Canvas<u8, StencilBuffer>) -> Vec<Vec<u8>> {
+        (0..8)
+            .map(|y| (0..10).map(|x| canvas.get_point(x, y).unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn whole_source() {
+        let mut canvas = new_canvas();
+        canvas.blit(&source(), 1, 2);
+        for y in 0..8 {
+            for x in 0..10 {
+                let inside = (1..6).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { (10 * (y - 2) + x) as u8 } else { 99 };
+                assert_eq!(canvas.get_point(x, y), Some(expected), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn region_with_each_flip() {
+        // The 3x2 region starting at (1, 1) holds 12 13 14 / 22 23 24
+        let expected = [
+            (Flip::None, [[12, 13, 14], [22, 23, 24]]),
+            (Flip::Horizontal, [[14, 13, 12], [24, 23, 22]]),
+            (Flip::Vertical, [[22, 23, 24], [12, 13, 14]]),
+            (Flip::Both, [[24, 23, 22], [14, 13, 12]]),
+        ];
+
+        for &(flip, copied) in expected.iter() {
+            let mut canvas = new_canvas();
+            canvas.blit_region(&source(), (1, 1, 3, 2), 2, 3, flip);
+
+            for (y, row) in values(&canvas).iter().enumerate() {
+                for (x, &value) in row.iter().enumerate() {
+                    let expected = if (2..5).contains(&x) && (3..5).contains(&y) {
+                        copied[y - 3][x - 2]
+                    } else {
+                        99
+                    };
+                    assert_eq!(value, expected, "{:?} at ({}, {})", flip, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn region_off_the_source() {
+        // Only the part of the region inside of the source is copied, and
+        // the rest of the area is left alone
+        let mut canvas = new_canvas();
+        canvas.blit_region(&source(), (3, 2, 4, 3), 0, 0, Flip::None);
+        let rows = values(&canvas);
+        assert_eq!(rows[0][..5], [24, 25, 99, 99, 99]);
+        assert_eq!(rows[1][..5], [34, 35, 99, 99, 99]);
+        assert_eq!(rows[2][..5], [99, 99, 99, 99, 99]);
+
+        // Flipping moves the missing part to the other side
+        let mut canvas = new_canvas();
+        canvas.blit_region(&source(), (3, 2, 4, 3), 0, 0, Flip::Both);
+        let rows = values(&canvas);
+        assert_eq!(rows[0][..5], [99, 99, 99, 99, 99]);
+        assert_eq!(rows[1][..5], [99, 99, 35, 34, 99]);
+        assert_eq!(rows[2][..5], [99, 99, 25, 24, 99]);
+
+        // Off the top left of the source too
+        let mut canvas = new_canvas();
+        canvas.blit_region(&source(), (-1, -1, 2, 2), 4, 4, Flip::None);
+        let rows = values(&canvas);
+        assert_eq!(rows[4][3..7], [99, 99, 99, 99]);
+        assert_eq!(rows[5][3..7], [99, 99, 1, 99]);
+    }
+
+    #[test]
+    fn empty_region() {
+        let mut canvas = new_canvas();
+        canvas.blit_region(&source(), (0, 0, 0, 3), 0, 0, Flip::None);
+        canvas.blit_region(&source(), (0, 0, 3, -1), 0, 0, Flip::None);
+        assert!(values(&canvas).iter().flatten().all(|&value| value == 99));
+    }
+
+    #[test]
+    fn transformed() {
+        // Moving the canvas is the same as moving the point
+        let mut moved = new_canvas();
+        moved.translate(2.0, 1.0);
+        moved.blit_region(&source(), (1, 1, 3, 2), 1, 2, Flip::Horizontal);
+        let mut plain = new_canvas();
+        plain.blit_region(&source(), (1, 1, 3, 2), 3, 3, Flip::Horizontal);
+        assert_eq!(values(&moved), values(&plain));
+
+        // Doubling the size covers each source pixel with 2x2 canvas pixels,
+        // starting from the middle of the first one
+        let mut doubled = new_canvas();
+        doubled.scale(2.0, 2.0);
+        doubled.blit_region(&source(), (0, 0, 2, 2), 1, 1, Flip::None);
+        let rows = values(&doubled);
+        assert_eq!(rows[1][..6], [99, 1, 1, 2, 2, 99]);
+        assert_eq!(rows[2][..6], [99, 1, 1, 2, 2, 99]);
+        assert_eq!(rows[3][..6], [99, 11, 11, 12, 12, 99]);
+        assert_eq!(rows[4][..6], [99, 11, 11, 12, 12, 99]);
+        assert_eq!(rows[5][..6], [99, 99, 99, 99, 99, 99]);
+    }
+}
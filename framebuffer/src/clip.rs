@@ -0,0 +1,263 @@
+use crate::path::path_contours;
+use crate::polygon::scan_contours;
+use crate::{Canvas, GraphicBuffer, Path, StencilBuffer};
+use std::rc::Rc;
+
+/// The part of the buffer that drawing is limited to. A pixel has to be
+/// inside of both the rectangle and the mask, when they're set.
+#[derive(Clone)]
+pub(crate) struct Clip {
+    /// The pixels from (left, top) up to but not including (right, bottom)
+    rect: Option<(i64, i64, i64, i64)>,
+    /// Only the pixels where the mask is nonzero can be drawn. This is shared
+    /// with the saved states, which is why it's never changed in place.
+    mask: Option<Rc<StencilBuffer>>,
+}
+
+impl Clip {
+    /// Creates a clip that doesn't limit drawing at all
+    pub fn new() -> Clip {
+        Clip {
+            rect: None,
+            mask: None,
+        }
+    }
+
+    /// Checks whether the given pixel can be drawn on
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        if let Some((left, top, right, bottom)) = self.rect {
+            if x < left || x >= right || y < top || y >= bottom {
+                return false;
+            }
+        }
+
+        match &self.mask {
+            Some(mask) => mask.get_point(x, y).is_some_and(|value| value != 0),
+            None => true,
+        }
+    }
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Limits later drawing to the given region, within whatever was already
+    /// allowed by the clip. The region goes through the current transform the
+    /// same way as fill_rect.
+    pub fn clip_rect(&mut self, x: i64, y: i64, width: i64, height: i64) {
+        if self.is_transformed() {
+            // The region covers the whole of its outer pixels, the same as
+            // the one drawn by fill_rect
+            let (left, top) = (x as f64 - 0.5, y as f64 - 0.5);
+            let (right, bottom) = (left + width as f64, top + height as f64);
+
+            let mut path = Path::new();
+            path.move_to(left, top);
+            path.line_to(right, top);
+            path.line_to(right, bottom);
+            path.line_to(left, bottom);
+            path.close();
+            self.clip_path(&path);
+            return;
+        }
+
+        let (mut left, mut top) = (x, y);
+        let (mut right, mut bottom) = (x + width.max(0), y + height.max(0));
+        if let Some(rect) = self.clip.rect {
+            left = left.max(rect.0);
+            top = top.max(rect.1);
+            right = right.min(rect.2);
+            bottom = bottom.min(rect.3);
+        }
+
+        self.clip.rect = Some((left, top, right, bottom));
+    }
+
+    /// Limits later drawing to the inside of the path, within whatever was
+    /// already allowed by the clip. The path goes through the current
+    /// transform and the current fill rule decides which parts are inside.
+    pub fn clip_path(&mut self, path: &Path) {
+        let transform = self.transform;
+        let contours = path_contours(path.flatten(|point| transform.apply(point)));
+
+        let mut mask = StencilBuffer::new(self.buffer.width(), self.buffer.height());
+        let clip = &self.clip;
+        scan_contours(&contours, self.fill_rule, |py, left, right| {
+            for px in left..=right {
+                if clip.contains(px, py) {
+                    mask.put_point(px, py, 255);
+                }
+            }
+        });
+
+        self.clip.mask = Some(Rc::new(mask));
+    }
+
+    /// Limits later drawing to the pixels where the given mask is nonzero,
+    /// within whatever was already allowed by the clip. The mask lines up with
+    /// the buffer and isn't affected by the current transform.
+    pub fn clip_mask(&mut self, mask: &StencilBuffer) {
+        let (width, height) = (self.buffer.width(), self.buffer.height());
+        let mut combined = StencilBuffer::new(width, height);
+        for py in 0..height as i64 {
+            for px in 0..width as i64 {
+                let inside = mask.get_point(px, py).is_some_and(|value| value != 0);
+                if inside && self.clip.contains(px, py) {
+                    combined.put_point(px, py, 255);
+                }
+            }
+        }
+
+        self.clip.mask = Some(Rc::new(combined));
+    }
+
+    /// Removes the clip, so that drawing can reach the whole buffer again
+    pub fn reset_clip(&mut self) {
+        self.clip = Clip::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty 12x10 canvas that marks the pixels it draws with 1
+    fn new_canvas() -> Canvas<u8, StencilBuffer> {
+        Canvas::new(StencilBuffer::new(12, 10), 1, 1)
+    }
+
+    /// Lists the pixels that have been drawn on
+    fn drawn(canvas: &Canvas<u8, StencilBuffer>) -> Vec<(i64, i64)> {
+        let mut pixels = Vec::new();
+        for y in 0..canvas.height() as i64 {
+            for x in 0..canvas.width() as i64 {
+                if canvas.get_point(x, y) != Some(0) {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Lists the pixels inside of the (left, top, right, bottom) rectangle
+    fn inside(left: i64, top: i64, right: i64, bottom: i64) -> Vec<(i64, i64)> {
+        (top..bottom)
+            .flat_map(|y| (left..right).map(move |x| (x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn rect_limits_drawing() {
+        let mut canvas = new_canvas();
+        canvas.clip_rect(2, 3, 4, 5);
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(2, 3, 6, 8));
+    }
+
+    #[test]
+    fn rect_edges() {
+        // The first row and column are inside, the ones just past the end
+        // aren't
+        let mut clip = Clip::new();
+        clip.rect = Some((2, 3, 6, 8));
+        assert!(clip.contains(2, 3));
+        assert!(clip.contains(5, 7));
+        assert!(!clip.contains(1, 3));
+        assert!(!clip.contains(2, 2));
+        assert!(!clip.contains(6, 7));
+        assert!(!clip.contains(5, 8));
+    }
+
+    #[test]
+    fn rects_intersect() {
+        let mut canvas = new_canvas();
+        canvas.clip_rect(2, 3, 6, 5);
+        canvas.clip_rect(5, 1, 10, 4);
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(5, 3, 8, 5));
+
+        // Rectangles that don't touch leave nothing to draw on
+        canvas.clip_rect(0, 0, 2, 2);
+        canvas.fill_rect(0, 0, 12, 10);
+        assert_eq!(drawn(&canvas), inside(5, 3, 8, 5));
+    }
+
+    #[test]
+    fn transformed_rect_covers_the_same_pixels() {
+        let mut canvas = new_canvas();
+        canvas.translate(3.0, 2.0);
+        canvas.clip_rect(-1, 1, 4, 5);
+        canvas.reset_transform();
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(2, 3, 6, 8));
+    }
+
+    #[test]
+    fn rect_and_mask_combine() {
+        // A checkerboard, so that every other pixel is masked out
+        let mut mask = StencilBuffer::new(12, 10);
+        for y in 0..10 {
+            for x in 0..12 {
+                mask.put_point(x, y, ((x + y) % 2 * 200) as u8);
+            }
+        }
+        let expected: Vec<(i64, i64)> = inside(2, 3, 6, 8)
+            .into_iter()
+            .filter(|&(x, y)| (x + y) % 2 == 1)
+            .collect();
+
+        let mut canvas = new_canvas();
+        canvas.clip_rect(2, 3, 4, 5);
+        canvas.clip_mask(&mask);
+        canvas.fill();
+        assert_eq!(drawn(&canvas), expected);
+
+        // The order they're applied in doesn't matter
+        let mut canvas = new_canvas();
+        canvas.clip_mask(&mask);
+        canvas.clip_rect(2, 3, 4, 5);
+        canvas.fill();
+        assert_eq!(drawn(&canvas), expected);
+    }
+
+    #[test]
+    fn path_clip_within_rect() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0);
+        path.line_to(12.0, 0.0);
+        path.line_to(0.0, 12.0);
+        path.close();
+
+        let mut canvas = new_canvas();
+        canvas.clip_rect(0, 4, 12, 6);
+        canvas.clip_path(&path);
+        canvas.fill();
+        let expected: Vec<(i64, i64)> = inside(0, 4, 12, 10)
+            .into_iter()
+            .filter(|&(x, y)| x + y < 12)
+            .collect();
+        assert_eq!(drawn(&canvas), expected);
+    }
+
+    #[test]
+    fn restore_brings_back_the_clip() {
+        let mut canvas = new_canvas();
+        canvas.clip_rect(1, 1, 10, 8);
+        canvas.save();
+        canvas.clip_rect(4, 4, 2, 2);
+        canvas.save();
+        canvas.clip_mask(&StencilBuffer::new(12, 10));
+        canvas.fill();
+        assert!(drawn(&canvas).is_empty());
+
+        canvas.restore();
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(4, 4, 6, 6));
+
+        canvas.restore();
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(1, 1, 11, 9));
+
+        canvas.reset_clip();
+        canvas.fill();
+        assert_eq!(drawn(&canvas), inside(0, 0, 12, 10));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{Color, ColorSpace, Gradient};
+
+/// The number of colors that every colormap is broken down into
+const TABLE_SIZE: usize = 256;
+
+/*
+Ref: Matt Zucker, "Colormap fits", Shadertoy
+
+The matplotlib maps are stored as sixth degree polynomial fits, one per
+channel, with the coefficients listed from the constant term up. They stay
+within a few levels of the original tables.
+ */
+const VIRIDIS: [[f64; 3]; 7] = [
+    [0.2777273272234177, 0.005407344544966578, 0.3340998053353061],
+    [0.1050930431085774, 1.404613529898575, 1.384590162594685],
+    [-0.3308618287255563, 0.214847559468213, 0.09509516302823659],
+    [-4.634230498983486, -5.799100973351585, -19.33244095627987],
+    [6.228269936347081, 14.17993336680509, 56.69055260068105],
+    [4.776384997670288, -13.74514537774601, -65.35303263337234],
+    [-5.435455855934631, 4.645852612178535, 26.3124352495832],
+];
+
+const PLASMA: [[f64; 3]; 7] = [
+    [0.05873234392399702, 0.02333670892565664, 0.5433401826748754],
+    [2.176514634195958, 0.2383834171260182, 0.7539604599784036],
+    [-2.689460476458034, -7.455851135738909, 3.110799939717086],
+    [6.130348345893603, 42.3461881477227, -28.51885465332158],
+    [-11.10743619062271, -82.66631109428045, 60.13984767418263],
+    [10.02306557647065, 71.4136177009535, -54.07218655560067],
+    [-3.658713842777788, -22.93153465461149, 18.19190778539828],
+];
+
+const MAGMA: [[f64; 3]; 7] = [
+    [
+        -0.002136485053939582,
+        -0.000749655052795221,
+        -0.005386127855323933,
+    ],
+    [0.2516605407371642, 0.6775232436837668, 2.494026599312351],
+    [8.353717279216625, -3.577719514958484, 0.3144679030132573],
+    [-27.66873308576866, 14.26473078096533, -13.64921318813922],
+    [52.17613981234068, -27.94360607168351, 12.94416944238394],
+    [-50.76852536473588, 29.04658282127291, 4.23415299384598],
+    [18.65570506591883, -11.48977351997711, -5.601961508734096],
+];
+
+const INFERNO: [[f64; 3]; 7] = [
+    [
+        0.0002189403691192265,
+        0.001651004631001012,
+        -0.01948089843709184,
+    ],
+    [0.1065134194856116, 0.5639564367884091, 3.932712388889277],
+    [11.60249308247187, -3.972853965665698, -15.9423941062914],
+    [-41.70399613139459, 17.43639888205313, 44.35414519872813],
+    [77.162935699427, -33.40235894210092, -81.80730925738993],
+    [-71.31942824499214, 32.62606426397723, 73.20951985803202],
+    [25.13112622477341, -12.24266895238567, -23.07032500287172],
+];
+
+/// Evenly spaced samples of cividis, which is close enough to a straight line
+/// through its colors that mixing between them stays near the original
+const CIVIDIS: [(u8, u8, u8); 5] = [
+    (0x00, 0x20, 0x4d),
+    (0x41, 0x4d, 0x6b),
+    (0x7c, 0x7b, 0x78),
+    (0xbc, 0xaf, 0x6f),
+    (0xff, 0xea, 0x46),
+];
+
+/// Converts a value from 0 to 1 into an 8-bit channel
+fn to_channel(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Evaluates a polynomial fit at the given ratio
+fn polynomial(coefficients: &[[f64; 3]; 7], ratio: f64) -> Color {
+    let channel = |index: usize| {
+        coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |sum, terms| sum * ratio + terms[index])
+    };
+    Color::rgb(
+        to_channel(channel(0)),
+        to_channel(channel(1)),
+        to_channel(channel(2)),
+    )
+}
+
+/// Evaluates Turbo at the given ratio
+fn turbo(ratio: f64) -> Color {
+    /*
+    Ref: Anton Mikhailov, "Turbo, An Improved Rainbow Colormap for
+    Visualization", Google AI Blog, 2019
+
+    This is the polynomial approximation published along with the map. It
+    follows the table closely through the middle but drifts away from it a
+    little at both ends.
+     */
+    let (x, x2) = (ratio, ratio * ratio);
+    let (x3, x4) = (x2 * x, x2 * x2);
+    let x5 = x4 * x;
+
+    let r = 0.13572138 + 4.61539260 * x - 42.66032258 * x2 + 132.13108234 * x3 - 152.94239396 * x4
+        + 59.28637943 * x5;
+    let g = 0.09140261 + 2.19418839 * x + 4.84296658 * x2 - 14.18503333 * x3
+        + 4.27729857 * x4
+        + 2.82956604 * x5;
+    let b = 0.10667330 + 12.64194608 * x - 60.58204836 * x2 + 110.36276771 * x3 - 89.90310912 * x4
+        + 27.34824973 * x5;
+
+    Color::rgb(to_channel(r), to_channel(g), to_channel(b))
+}
+
+/// Maps values from 0 to 1 onto a range of colors, for showing things like
+/// heat or density
+#[derive(Clone, PartialEq)]
+pub struct Colormap {
+    table: Vec<Color>,
+}
+
+impl Colormap {
+    /// Builds a colormap by sampling the function at evenly spaced ratios
+    fn tabulate<F: Fn(f64) -> Color>(sample: F) -> Colormap {
+        let table = (0..TABLE_SIZE)
+            .map(|i| sample(i as f64 / (TABLE_SIZE - 1) as f64))
+            .collect();
+        Colormap { table }
+    }
+
+    /// Dark blue through green to yellow. This is a good default, since it's
+    /// perceptually uniform and readable with most kinds of color blindness.
+    pub fn viridis() -> Colormap {
+        Colormap::tabulate(|ratio| polynomial(&VIRIDIS, ratio))
+    }
+
+    /// Black through purple and orange to pale yellow
+    pub fn magma() -> Colormap {
+        Colormap::tabulate(|ratio| polynomial(&MAGMA, ratio))
+    }
+
+    /// Black through purple and orange to bright yellow
+    pub fn inferno() -> Colormap {
+        Colormap::tabulate(|ratio| polynomial(&INFERNO, ratio))
+    }
+
+    /// Dark blue through magenta and orange to yellow
+    pub fn plasma() -> Colormap {
+        Colormap::tabulate(|ratio| polynomial(&PLASMA, ratio))
+    }
+
+    /// Dark blue through gray to yellow, made to look nearly the same to
+    /// people with red-green color blindness as to everyone else
+    pub fn cividis() -> Colormap {
+        let stops: Vec<(f64, Color)> = CIVIDIS
+            .iter()
+            .enumerate()
+            .map(|(i, &(r, g, b))| (i as f64 / 4.0, Color::rgb(r, g, b)))
+            .collect();
+        Colormap::from_stops(&stops, ColorSpace::Srgb)
+    }
+
+    /// A rainbow running from dark blue through green to dark red, which
+    /// shows fine detail well but isn't perceptually uniform
+    pub fn turbo() -> Colormap {
+        Colormap::tabulate(turbo)
+    }
+
+    /// Builds a colormap from (offset, color) stops, where the offsets run
+    /// from 0 to 1, mixing between them in the given space
+    pub fn from_stops(stops: &[(f64, Color)], space: ColorSpace) -> Colormap {
+        let mut gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0);
+        gradient.set_color_space(space);
+        for &(offset, color) in stops {
+            gradient.add_stop(offset, color);
+        }
+
+        Colormap::tabulate(|ratio| gradient.sample(ratio))
+    }
+
+    /// Returns the same colormap running the other way
+    pub fn reversed(&self) -> Colormap {
+        Colormap {
+            table: self.table.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Gets the color for a value from 0 to 1. Values outside of that range
+    /// get the color at the nearest end.
+    pub fn sample(&self, value: f64) -> Color {
+        let position = value.clamp(0.0, 1.0) * (TABLE_SIZE - 1) as f64;
+        let index = (position.floor() as usize).min(TABLE_SIZE - 2);
+        self.table[index].interpolate(
+            self.table[index + 1],
+            position - index as f64,
+            ColorSpace::Srgb,
+        )
+    }
+}
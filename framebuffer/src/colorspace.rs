@@ -0,0 +1,461 @@
+use crate::Color;
+
+/// A color as hue, saturation and value. The hue is in degrees from 0 to 360,
+/// starting at red, and the others run from 0 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hsv {
+    pub h: f64,
+    pub s: f64,
+    pub v: f64,
+}
+
+/// A color as hue, saturation and lightness. The hue is in degrees from 0 to
+/// 360, starting at red, and the others run from 0 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hsl {
+    pub h: f64,
+    pub s: f64,
+    pub l: f64,
+}
+
+/// A color as amounts of red, green and blue light, from 0 to 1. Unlike the
+/// channels of a Color these are proportional to the light itself, so adding
+/// and averaging them behaves physically.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LinearRgb {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+/// A color in Björn Ottosson's OKLab space, which is laid out so that equal
+/// distances look like roughly equal differences. The lightness runs from 0 to
+/// 1, while a (green to red) and b (blue to yellow) stay within about 0.4 of
+/// 0 for colors that a Color can show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Oklab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// An OKLab color as lightness, chroma and hue, where the chroma is the
+/// distance from gray and the hue is in degrees from 0 to 360
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Oklch {
+    pub l: f64,
+    pub c: f64,
+    pub h: f64,
+}
+
+/// The space that colors are mixed in when interpolating between them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorSpace {
+    /// Mixes the channels of the Colors directly, which is quick but tends
+    /// to go dark and muddy between very different colors
+    Srgb,
+    /// Mixes the amounts of light, which is how real light adds up but tends
+    /// to look too bright between very different colors
+    LinearRgb,
+    /// Goes around the hue circle the short way, keeping colors saturated
+    Hsv,
+    /// Goes around the hue circle the short way, keeping colors saturated
+    Hsl,
+    /// Mixes in OKLab, which gives even steps in brightness and no hue shifts
+    Oklab,
+    /// Goes around the OKLch hue circle the short way, which gives even
+    /// steps in brightness while keeping colors vivid
+    Oklch,
+}
+
+/// Converts an 8-bit sRGB channel into an amount of light
+fn to_linear(channel: u8) -> f64 {
+    let value = channel as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts an amount of light into an 8-bit sRGB channel
+fn from_linear(value: f64) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    to_channel(encoded)
+}
+
+/// Converts a value from 0 to 1 into an 8-bit channel
+fn to_channel(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Mixes two values by the ratio
+fn lerp(from: f64, to: f64, ratio: f64) -> f64 {
+    from + (to - from) * ratio
+}
+
+/// Mixes two hues in degrees by the ratio, going around the circle whichever
+/// way is shorter
+fn lerp_hue(from: f64, to: f64, ratio: f64) -> f64 {
+    let mut delta = (to - from).rem_euclid(360.0);
+    if delta > 180.0 {
+        delta -= 360.0;
+    }
+    (from + delta * ratio).rem_euclid(360.0)
+}
+
+/// Picks the hues to mix between. A gray has no real hue, so it takes the
+/// other color's hue instead of pulling the mix toward red.
+fn pick_hues(from: (f64, bool), to: (f64, bool)) -> (f64, f64) {
+    match (from, to) {
+        ((_, true), (hue, false)) => (hue, hue),
+        ((hue, false), (_, true)) => (hue, hue),
+        ((from, _), (to, _)) => (from, to),
+    }
+}
+
+/// Finds the hue in degrees, along with the largest and smallest channel, of
+/// a color with channels from 0 to 1
+fn hue_range(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (hue, max, min)
+}
+
+/// Builds a color from its hue in degrees, its chroma (the difference between
+/// its largest and smallest channels) and its smallest channel
+fn from_hue(hue: f64, chroma: f64, min: f64) -> Color {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let second = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+
+    Color::rgb(
+        to_channel(r + min),
+        to_channel(g + min),
+        to_channel(b + min),
+    )
+}
+
+impl Hsv {
+    /// Creates a new color from its hue, saturation and value
+    pub fn new(h: f64, s: f64, v: f64) -> Hsv {
+        Hsv { h, s, v }
+    }
+
+    /// Converts into an opaque Color
+    pub fn to_color(&self) -> Color {
+        let (s, v) = (self.s.clamp(0.0, 1.0), self.v.clamp(0.0, 1.0));
+        let chroma = v * s;
+        from_hue(self.h, chroma, v - chroma)
+    }
+}
+
+impl Hsl {
+    /// Creates a new color from its hue, saturation and lightness
+    pub fn new(h: f64, s: f64, l: f64) -> Hsl {
+        Hsl { h, s, l }
+    }
+
+    /// Converts into an opaque Color
+    pub fn to_color(&self) -> Color {
+        let (s, l) = (self.s.clamp(0.0, 1.0), self.l.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        from_hue(self.h, chroma, l - chroma / 2.0)
+    }
+}
+
+impl LinearRgb {
+    /// Creates a new color from its amounts of red, green and blue light
+    pub fn new(r: f64, g: f64, b: f64) -> LinearRgb {
+        LinearRgb { r, g, b }
+    }
+
+    /// Converts into an opaque Color, cutting off any amounts outside of 0
+    /// to 1
+    pub fn to_color(&self) -> Color {
+        Color::rgb(
+            from_linear(self.r),
+            from_linear(self.g),
+            from_linear(self.b),
+        )
+    }
+
+    /// Converts into OKLab
+    pub fn to_oklab(&self) -> Oklab {
+        /*
+        Ref: Björn Ottosson, "A perceptual color space for image processing"
+
+        The light is moved into cone responses (l, m, s) by one matrix, these
+        are compressed with a cube root, and a second matrix moves them into
+        lightness and the two color axes.
+         */
+        let (r, g, b) = (self.r, self.g, self.b);
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+        Oklab {
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        }
+    }
+}
+
+impl Oklab {
+    /// Creates a new color from its lightness and color axes
+    pub fn new(l: f64, a: f64, b: f64) -> Oklab {
+        Oklab { l, a, b }
+    }
+
+    /// Converts into an opaque Color. Colors that a Color can't show are cut
+    /// off, channel by channel.
+    pub fn to_color(&self) -> Color {
+        self.to_linear().to_color()
+    }
+
+    /// Converts into linear RGB, undoing each step of LinearRgb::to_oklab.
+    /// Colors outside of what a Color can show have amounts outside of 0 to 1.
+    pub fn to_linear(&self) -> LinearRgb {
+        let l = (self.l + 0.3963377774 * self.a + 0.2158037573 * self.b).powi(3);
+        let m = (self.l - 0.1055613458 * self.a - 0.0638541728 * self.b).powi(3);
+        let s = (self.l - 0.0894841775 * self.a - 1.2914855480 * self.b).powi(3);
+
+        LinearRgb {
+            r: 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+            g: -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+            b: -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+        }
+    }
+
+    /// Converts into lightness, chroma and hue
+    pub fn to_oklch(&self) -> Oklch {
+        Oklch {
+            l: self.l,
+            c: self.a.hypot(self.b),
+            h: self.b.atan2(self.a).to_degrees().rem_euclid(360.0),
+        }
+    }
+}
+
+impl Oklch {
+    /// Creates a new color from its lightness, chroma and hue
+    pub fn new(l: f64, c: f64, h: f64) -> Oklch {
+        Oklch { l, c, h }
+    }
+
+    /// Converts into an opaque Color. Colors that a Color can't show are cut
+    /// off, channel by channel.
+    pub fn to_color(&self) -> Color {
+        self.to_oklab().to_color()
+    }
+
+    /// Converts back into OKLab's color axes
+    pub fn to_oklab(&self) -> Oklab {
+        let (sin, cos) = self.h.to_radians().sin_cos();
+        Oklab {
+            l: self.l,
+            a: self.c * cos,
+            b: self.c * sin,
+        }
+    }
+}
+
+impl Color {
+    /// Converts into hue, saturation and value, ignoring alpha
+    pub fn to_hsv(&self) -> Hsv {
+        let (r, g, b) = (self.r as f64, self.g as f64, self.b as f64);
+        let (h, max, min) = hue_range(r / 255.0, g / 255.0, b / 255.0);
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsv { h, s, v: max }
+    }
+
+    /// Converts into hue, saturation and lightness, ignoring alpha
+    pub fn to_hsl(&self) -> Hsl {
+        let (r, g, b) = (self.r as f64, self.g as f64, self.b as f64);
+        let (h, max, min) = hue_range(r / 255.0, g / 255.0, b / 255.0);
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        Hsl { h, s, l }
+    }
+
+    /// Converts into amounts of light, ignoring alpha
+    pub fn to_linear(&self) -> LinearRgb {
+        LinearRgb {
+            r: to_linear(self.r),
+            g: to_linear(self.g),
+            b: to_linear(self.b),
+        }
+    }
+
+    /// Converts into OKLab, ignoring alpha
+    pub fn to_oklab(&self) -> Oklab {
+        self.to_linear().to_oklab()
+    }
+
+    /// Converts into OKLab lightness, chroma and hue, ignoring alpha
+    pub fn to_oklch(&self) -> Oklch {
+        self.to_oklab().to_oklch()
+    }
+
+    /// Creates a new color between this color (at a ratio of 0) and the
+    /// other color (at a ratio of 1), mixed in the given space. Alpha is
+    /// always mixed directly.
+    pub fn interpolate(&self, other: Color, ratio: f64, space: ColorSpace) -> Color {
+        let mixed = match space {
+            ColorSpace::Srgb => Color::rgb(
+                lerp(self.r as f64, other.r as f64, ratio).round() as u8,
+                lerp(self.g as f64, other.g as f64, ratio).round() as u8,
+                lerp(self.b as f64, other.b as f64, ratio).round() as u8,
+            ),
+            ColorSpace::LinearRgb => {
+                let (from, to) = (self.to_linear(), other.to_linear());
+                LinearRgb::new(
+                    lerp(from.r, to.r, ratio),
+                    lerp(from.g, to.g, ratio),
+                    lerp(from.b, to.b, ratio),
+                )
+                .to_color()
+            }
+            ColorSpace::Hsv => {
+                let (from, to) = (self.to_hsv(), other.to_hsv());
+                let (h1, h2) = pick_hues((from.h, from.s == 0.0), (to.h, to.s == 0.0));
+                Hsv::new(
+                    lerp_hue(h1, h2, ratio),
+                    lerp(from.s, to.s, ratio),
+                    lerp(from.v, to.v, ratio),
+                )
+                .to_color()
+            }
+            ColorSpace::Hsl => {
+                let (from, to) = (self.to_hsl(), other.to_hsl());
+                let (h1, h2) = pick_hues((from.h, from.s == 0.0), (to.h, to.s == 0.0));
+                Hsl::new(
+                    lerp_hue(h1, h2, ratio),
+                    lerp(from.s, to.s, ratio),
+                    lerp(from.l, to.l, ratio),
+                )
+                .to_color()
+            }
+            ColorSpace::Oklab => {
+                let (from, to) = (self.to_oklab(), other.to_oklab());
+                Oklab::new(
+                    lerp(from.l, to.l, ratio),
+                    lerp(from.a, to.a, ratio),
+                    lerp(from.b, to.b, ratio),
+                )
+                .to_color()
+            }
+            ColorSpace::Oklch => {
+                // Grays come out of the conversion with a tiny chroma from
+                // rounding, rather than exactly 0
+                let (from, to) = (self.to_oklch(), other.to_oklch());
+                let (h1, h2) = pick_hues((from.h, from.c < 1e-4), (to.h, to.c < 1e-4));
+                Oklch::new(
+                    lerp(from.l, to.l, ratio),
+                    lerp(from.c, to.c, ratio),
+                    lerp_hue(h1, h2, ratio),
+                )
+                .to_color()
+            }
+        };
+
+        let alpha = lerp(self.alpha as f64, other.alpha as f64, ratio).round() as u8;
+        Color::rgba(mixed.r, mixed.g, mixed.b, alpha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that no channel of the color moved by more than one step
+    fn close(color: Color, expected: Color) -> bool {
+        let near = |a: u8, b: u8| (a as i16 - b as i16).abs() <= 1;
+        near(color.r, expected.r) && near(color.g, expected.g) && near(color.b, expected.b)
+    }
+
+    /// Goes through an even spread of colors, including the corners of the
+    /// RGB cube
+    fn sample_colors() -> impl Iterator<Item = Color> {
+        let steps = (0..=255).step_by(5);
+        steps.clone().flat_map(move |r| {
+            let steps = steps.clone();
+            steps
+                .clone()
+                .flat_map(move |g| steps.clone().map(move |b| Color::rgb(r, g, b)))
+        })
+    }
+
+    #[test]
+    fn linear_round_trip() {
+        for channel in 0..=255 {
+            assert_eq!(from_linear(to_linear(channel)), channel);
+        }
+
+        assert_eq!(to_linear(0), 0.0);
+        assert_eq!(to_linear(255), 1.0);
+        assert!((to_linear(128) - 0.2158605).abs() < 1e-6);
+    }
+
+    #[test]
+    fn oklab_round_trip() {
+        for color in sample_colors() {
+            let lab = color.to_oklab();
+            assert!(close(lab.to_color(), color), "{:?}", lab);
+            assert!(close(lab.to_oklch().to_color(), color), "{:?}", lab);
+        }
+    }
+
+    #[test]
+    fn oklab_known_values() {
+        let white = Color::rgb(255, 255, 255).to_oklab();
+        assert!((white.l - 1.0).abs() < 1e-6);
+        assert!(white.a.abs() < 1e-6 && white.b.abs() < 1e-6);
+
+        let black = Color::rgb(0, 0, 0).to_oklab();
+        assert!(black.l.abs() < 1e-9);
+
+        // Ref: Björn Ottosson, "A perceptual color space for image processing"
+        let red = LinearRgb::new(1.0, 0.0, 0.0).to_oklab();
+        assert!((red.l - 0.627955).abs() < 1e-4);
+        assert!((red.a - 0.224863).abs() < 1e-4);
+        assert!((red.b - 0.125846).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hue_round_trip() {
+        for color in sample_colors() {
+            assert!(close(color.to_hsv().to_color(), color));
+            assert!(close(color.to_hsl().to_color(), color));
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use crate::polygon::{scan_contours, FillRule};
+use crate::{Canvas, GraphicBuffer, Path};
+use std::collections::HashSet;
+
+/// How far a flattened curve may stray from the real curve, in pixels
+const TOLERANCE: f64 = 0.25;
+
+/// The longest a miter join can be, relative to half the line width, before
+/// it is cut off into a bevel. This is the same default that SVG uses.
+const MITER_LIMIT: f64 = 4.0;
+
+/// Adds points along a quadratic Bézier curve to the polyline, not including
+/// its starting point
+pub(crate) fn flatten_quadratic(
+    points: &mut Vec<(f64, f64)>,
+    start: (f64, f64),
+    control: (f64, f64),
+    end: (f64, f64),
+) {
+    /*
+    This uses Wang's formula. Splitting a curve of degree d into n pieces
+    with evenly spaced t values keeps every piece within this distance of the
+    real curve:
+
+    d * (d - 1) / 8 * M / n^2
+
+    Where M is the length of the longest second difference of the control
+    points (a quadratic curve only has one). Solving for n gives the fewest
+    pieces that stay within the tolerance, so gentle curves get a few
+    segments and sharp ones get many.
+     */
+    let ddx = start.0 - 2.0 * control.0 + end.0;
+    let ddy = start.1 - 2.0 * control.1 + end.1;
+    let pieces = segment_count(0.25 * (ddx * ddx + ddy * ddy).sqrt());
+
+    for i in 1..=pieces {
+        let t = i as f64 / pieces as f64;
+        let mt = 1.0 - t;
+        let (a, b, c) = (mt * mt, 2.0 * mt * t, t * t);
+        points.push((
+            a * start.0 + b * control.0 + c * end.0,
+            a * start.1 + b * control.1 + c * end.1,
+        ));
+    }
+}
+
+/// Adds points along a cubic Bézier curve to the polyline, not including its
+/// starting point
+pub(crate) fn flatten_cubic(
+    points: &mut Vec<(f64, f64)>,
+    start: (f64, f64),
+    control: (f64, f64),
+    control2: (f64, f64),
+    end: (f64, f64),
+) {
+    let dd = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        let ddx = a.0 - 2.0 * b.0 + c.0;
+        let ddy = a.1 - 2.0 * b.1 + c.1;
+        (ddx * ddx + ddy * ddy).sqrt()
+    };
+    let largest = dd(start, control, control2).max(dd(control, control2, end));
+    let pieces = segment_count(0.75 * largest);
+
+    for i in 1..=pieces {
+        let t = i as f64 / pieces as f64;
+        let mt = 1.0 - t;
+        let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+        points.push((
+            a * start.0 + b * control.0 + c * control2.0 + d * end.0,
+            a * start.1 + b * control.1 + c * control2.1 + d * end.1,
+        ));
+    }
+}
+
+/// Picks the number of evenly spaced pieces needed to keep a curve within the
+/// tolerance, given the curve's error bound for a single piece
+fn segment_count(bound: f64) -> usize {
+    ((bound / TOLERANCE).sqrt().ceil() as usize).clamp(1, 1024)
+}
+
+/// Visits the pixels of a one pixel wide line between the two points, using
+/// the same stepping as stroke_line
+fn line_pixels<F>(x: i64, y: i64, x2: i64, y2: i64, mut visit: F)
+where
+    F: FnMut(i64, i64),
+{
+    let deltax = (x2 - x).abs();
+    let stepx = (x2 - x).signum();
+
+    let deltay = -(y2 - y).abs();
+    let stepy = (y2 - y).signum();
+
+    let mut error = deltax + deltay;
+
+    let mut px = x;
+    let mut py = y;
+    loop {
+        visit(px, py);
+
+        let next_error = 2 * error;
+        if next_error >= deltay {
+            if px == x2 {
+                break;
+            }
+
+            error += deltay;
+            px += stepx;
+        }
+
+        if next_error <= deltax {
+            if py == y2 {
+                break;
+            }
+
+            error += deltax;
+            py += stepy;
+        }
+    }
+}
+
+/// Returns the unit normal of the segment between the two points, or None if
+/// the points are the same
+fn normal(from: (f64, f64), to: (f64, f64)) -> Option<(f64, f64)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        None
+    } else {
+        Some((-dy / length, dx / length))
+    }
+}
+
+/// Removes repeated points from a polyline, and for closed polylines adds the
+/// first point to the end so that the last line leads back to it. Returns
+/// None if there aren't at least two distinct points.
+pub(crate) fn prepare_polyline(points: &[(f64, f64)], closed: bool) -> Option<Vec<(f64, f64)>> {
+    let mut points: Vec<(f64, f64)> = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 2 {
+        return None;
+    }
+    if closed {
+        points.push(points[0]);
+    }
+    Some(points)
+}
+
+/// Returns twice the signed area of the polygon, which is positive when its
+/// corners go counter-clockwise on the screen
+fn signed_area(points: &[(f64, f64)]) -> f64 {
+    let mut area = 0.0;
+    for (i, &(x, y)) in points.iter().enumerate() {
+        let (x2, y2) = points[(i + 1) % points.len()];
+        area += x2 * y - x * y2;
+    }
+    area
+}
+
+/// The outline of a wide polyline, as one four-sided piece around each line
+/// plus triangles filling in the corners that are too sharp to miter
+pub(crate) struct StrokeOutline {
+    pub pieces: Vec<Vec<(f64, f64)>>,
+    /// Each bevel, along with the index of the point it belongs to
+    pub bevels: Vec<(Vec<(f64, f64)>, usize)>,
+}
+
+impl StrokeOutline {
+    /// Builds the outline around a polyline from prepare_polyline
+    pub(crate) fn new(points: &[(f64, f64)], closed: bool, half_width: f64) -> StrokeOutline {
+        /*
+        Each point is offset by half the line width to either side. In the
+        middle of the polyline, where two lines meet with normals n0 and n1,
+        the offset is along the miter direction, which keeps the edges of
+        both pieces parallel to their lines:
+
+        miter = (n0 + n1) * half_width / (1 + n0 . n1)
+
+        The miter's length relative to half_width is 1 / cos(angle / 2), which
+        goes to infinity as the lines fold back on themselves. Past the limit,
+        each piece gets its own square end at the point instead and the notch
+        on the outside of the corner is filled in with a triangle (a bevel).
+
+        Neighboring pieces share the corners where they meet, so they fit
+        together without gaps or overlaps along the outside of the curve.
+        The bevels are flipped to go around in the same direction as the
+        pieces, so that they can be filled together as one shape using the
+        non-zero rule.
+         */
+        let count = points.len() - 1;
+        let normals: Vec<(f64, f64)> = points
+            .windows(2)
+            .map(|pair| normal(pair[0], pair[1]).unwrap())
+            .collect();
+
+        // The offsets used at the start and end of each piece
+        let mut starts: Vec<(f64, f64)> = normals
+            .iter()
+            .map(|&(nx, ny)| (nx * half_width, ny * half_width))
+            .collect();
+        let mut ends = starts.clone();
+
+        let mut bevels = Vec::new();
+        let first_join = if closed { 0 } else { 1 };
+        for i in first_join..count {
+            let previous = (i + count - 1) % count;
+            let (px, py) = normals[previous];
+            let (nx, ny) = normals[i];
+            let dot = px * nx + py * ny;
+
+            if dot > -1.0 && (2.0 / (1.0 + dot)).sqrt() <= MITER_LIMIT {
+                let scale = half_width / (1.0 + dot);
+                let miter = ((px + nx) * scale, (py + ny) * scale);
+                starts[i] = miter;
+                ends[previous] = miter;
+            } else {
+                // The side that the polyline turns away from is the outside
+                let cross = px * ny - py * nx;
+                let side = if cross > 0.0 { -half_width } else { half_width };
+                let (x, y) = points[i];
+                let mut bevel = vec![
+                    (x, y),
+                    (x + px * side, y + py * side),
+                    (x + nx * side, y + ny * side),
+                ];
+                if signed_area(&bevel) > 0.0 {
+                    bevel.reverse();
+                }
+                bevels.push((bevel, i));
+            }
+        }
+
+        let pieces = (0..count)
+            .map(|i| {
+                let (x, y) = points[i];
+                let (x2, y2) = points[i + 1];
+                let (sx, sy) = starts[i];
+                let (ex, ey) = ends[i];
+                vec![
+                    (x + sx, y + sy),
+                    (x2 + ex, y2 + ey),
+                    (x2 - ex, y2 - ey),
+                    (x - sx, y - sy),
+                ]
+            })
+            .collect();
+
+        StrokeOutline { pieces, bevels }
+    }
+
+    /// Gathers all the pieces and bevels into a single list of contours, all
+    /// going around in the same direction
+    pub(crate) fn into_contours(self) -> Vec<Vec<(f64, f64)>> {
+        /*
+        Where the line bends more tightly than half its width, the inside
+        corners of a piece pass each other and it turns into a bow tie. Each
+        half of the bow tie goes around the other way, so it would cancel out
+        the pieces around it when they're all filled together. Splitting it
+        at the crossing gives two triangles that can be turned around to
+        match the rest.
+         */
+        let mut contours = Vec::with_capacity(self.pieces.len() + self.bevels.len());
+        for piece in self.pieces {
+            let (a, b, c, d) = (piece[0], piece[1], piece[2], piece[3]);
+            if let Some(x) = crossing(b, c, d, a) {
+                contours.push(vec![a, b, x]);
+                contours.push(vec![x, c, d]);
+            } else if let Some(x) = crossing(a, b, c, d) {
+                contours.push(vec![a, x, d]);
+                contours.push(vec![x, b, c]);
+            } else {
+                contours.push(piece);
+            }
+        }
+
+        for contour in contours.iter_mut() {
+            if signed_area(contour) > 0.0 {
+                contour.reverse();
+            }
+        }
+        contours.extend(self.bevels.into_iter().map(|(bevel, _)| bevel));
+        contours
+    }
+}
+
+/// Returns the point where the line from a to b crosses the line from c to
+/// d, or None if they don't cross
+fn crossing(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> Option<(f64, f64)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (dx2, dy2) = (d.0 - c.0, d.1 - c.1);
+    let denominator = dx * dy2 - dy * dx2;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let (ox, oy) = (c.0 - a.0, c.1 - a.1);
+    let t = (ox * dy2 - oy * dx2) / denominator;
+    let u = (ox * dy - oy * dx) / denominator;
+    if t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0 {
+        Some((a.0 + t * dx, a.1 + t * dy))
+    } else {
+        None
+    }
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Draws a connected series of straight lines through the points, using
+    /// the given line width and gradient(x, y, ratio) for each pixel, where
+    /// the ratio is the distance along the whole line. If closed is set, the
+    /// last point is joined back up with the first.
+    pub(crate) fn stroke_polyline<F>(
+        &mut self,
+        points: &[(f64, f64)],
+        closed: bool,
+        width: f64,
+        gradient: F,
+    ) where
+        F: Fn(i64, i64, f64) -> Element,
+    {
+        let points = match prepare_polyline(points, closed) {
+            Some(points) => points,
+            None => return,
+        };
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        distances.push(0.0);
+        for pair in points.windows(2) {
+            total += ((pair[1].0 - pair[0].0).powi(2) + (pair[1].1 - pair[0].1).powi(2)).sqrt();
+            distances.push(total);
+        }
+
+        // Transforms that only rotate can leave the width a hair off of 1
+        if (width - 1.0).abs() < 1e-9 {
+            self.thin_polyline(&points, &distances, total, closed, gradient);
+        } else {
+            self.wide_polyline(&points, &distances, total, closed, width, gradient);
+        }
+    }
+
+    /// Draws a polyline one pixel wide. Each pixel where two lines meet is
+    /// only drawn once, so that colors with transparency don't show the
+    /// joints.
+    fn thin_polyline<F>(
+        &mut self,
+        points: &[(f64, f64)],
+        distances: &[f64],
+        total: f64,
+        closed: bool,
+        gradient: F,
+    ) where
+        F: Fn(i64, i64, f64) -> Element,
+    {
+        let rounded: Vec<(i64, i64)> = points
+            .iter()
+            .map(|&(x, y)| (x.round() as i64, y.round() as i64))
+            .collect();
+
+        let last = rounded.len() - 2;
+        for (i, pair) in rounded.windows(2).enumerate() {
+            let ((x, y), (x2, y2)) = (pair[0], pair[1]);
+            let length = (((x2 - x).pow(2) + (y2 - y).pow(2)) as f64).sqrt();
+            line_pixels(x, y, x2, y2, |px, py| {
+                let at_start = (px, py) == (x, y);
+                let at_end = (px, py) == (x2, y2);
+                if (at_start && i > 0) || (at_end && closed && i == last) {
+                    return;
+                }
+
+                let along = if length == 0.0 {
+                    0.0
+                } else {
+                    (((px - x).pow(2) + (py - y).pow(2)) as f64).sqrt() / length
+                };
+                let distance = distances[i] + along * (distances[i + 1] - distances[i]);
+                let ratio = if total == 0.0 { 0.0 } else { distance / total };
+                self.put_point(px, py, gradient(px, py, ratio.clamp(0.0, 1.0)));
+            });
+        }
+    }
+
+    /// Draws a polyline wider than one pixel by filling in its outline. Each
+    /// piece of the outline is filled separately, so that the gradient can
+    /// follow the line that the piece belongs to, but each pixel is only
+    /// drawn once.
+    fn wide_polyline<F>(
+        &mut self,
+        points: &[(f64, f64)],
+        distances: &[f64],
+        total: f64,
+        closed: bool,
+        width: f64,
+        gradient: F,
+    ) where
+        F: Fn(i64, i64, f64) -> Element,
+    {
+        let outline = StrokeOutline::new(points, closed, width / 2.0);
+        let rule = FillRule::NonZero;
+
+        /*
+        Neighboring pieces overlap on the inside of each bend, and the bevels
+        overlap the pieces they join. A pixel is only drawn by the first
+        piece that reaches it, so that colors with transparency aren't
+        blended twice where the pieces meet.
+         */
+        let mut drawn = HashSet::new();
+
+        for (i, piece) in outline.pieces.into_iter().enumerate() {
+            let (x, y) = points[i];
+            let (x2, y2) = points[i + 1];
+            let length = distances[i + 1] - distances[i];
+            let (dx, dy) = ((x2 - x) / length, (y2 - y) / length);
+
+            scan_contours(&[piece], rule, |py, left, right| {
+                for px in left..=right {
+                    let along = ((px as f64 - x) * dx + (py as f64 - y) * dy).clamp(0.0, length);
+                    let ratio = (distances[i] + along) / total;
+                    if drawn.insert((px, py)) {
+                        self.put_point(px, py, gradient(px, py, ratio));
+                    }
+                }
+            });
+        }
+
+        for (bevel, i) in outline.bevels {
+            let ratio = distances[i] / total;
+            scan_contours(&[bevel], rule, |py, left, right| {
+                for px in left..=right {
+                    if drawn.insert((px, py)) {
+                        self.put_point(px, py, gradient(px, py, ratio));
+                    }
+                }
+            });
+        }
+    }
+
+    /// Draws a quadratic Bézier curve from the start to the end point, bending
+    /// toward the control point, using the current stroke color
+    pub fn stroke_quadratic(&mut self, start: (i64, i64), control: (i64, i64), end: (i64, i64)) {
+        let stroke = self.stroke;
+        self.gstroke_quadratic(start, control, end, |_| stroke);
+    }
+
+    /// Draws a quadratic Bézier curve using the given gradient(ratio), where
+    /// the ratio is the distance along the curve
+    pub fn gstroke_quadratic<F>(
+        &mut self,
+        start: (i64, i64),
+        control: (i64, i64),
+        end: (i64, i64),
+        gradient: F,
+    ) where
+        F: Fn(f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.move_to(start.0 as f64, start.1 as f64);
+        path.quad_to(
+            control.0 as f64,
+            control.1 as f64,
+            end.0 as f64,
+            end.1 as f64,
+        );
+        self.stroke_path_with(&path, self.line_width, |_, _, ratio| gradient(ratio));
+    }
+
+    /// Draws a cubic Bézier curve from the start to the end point, leaving the
+    /// start toward the first control point and arriving at the end from the
+    /// second, using the current stroke color
+    pub fn stroke_cubic(
+        &mut self,
+        start: (i64, i64),
+        control: (i64, i64),
+        control2: (i64, i64),
+        end: (i64, i64),
+    ) {
+        let stroke = self.stroke;
+        self.gstroke_cubic(start, control, control2, end, |_| stroke);
+    }
+
+    /// Draws a cubic Bézier curve using the given gradient(ratio), where the
+    /// ratio is the distance along the curve
+    pub fn gstroke_cubic<F>(
+        &mut self,
+        start: (i64, i64),
+        control: (i64, i64),
+        control2: (i64, i64),
+        end: (i64, i64),
+        gradient: F,
+    ) where
+        F: Fn(f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.move_to(start.0 as f64, start.1 as f64);
+        path.cubic_to(
+            control.0 as f64,
+            control.1 as f64,
+            control2.0 as f64,
+            control2.1 as f64,
+            end.0 as f64,
+            end.1 as f64,
+        );
+        self.stroke_path_with(&path, self.line_width, |_, _, ratio| gradient(ratio));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, FrameBuffer};
+
+    /// Strokes the polyline in half transparent white on black, and checks
+    /// that every pixel was either left alone or blended exactly once
+    fn check_blended_once(points: &[(f64, f64)], closed: bool, width: f64) {
+        let black = Color::black();
+        let mut canvas = Canvas::new(FrameBuffer::new(60, 60), black, black);
+        let color = Color::rgba(255, 255, 255, 128);
+        canvas.stroke_polyline(points, closed, width, |_, _, _| color);
+
+        let once = black.blend(color);
+        let mut drawn = 0;
+        for py in 0..60 {
+            for px in 0..60 {
+                let pixel = canvas.get_point(px, py).unwrap();
+                if pixel.r != 0 {
+                    assert_eq!(pixel.r, once.r, "({}, {})", px, py);
+                    drawn += 1;
+                }
+            }
+        }
+        assert!(drawn > 0);
+    }
+
+    #[test]
+    fn mitered_bend_blended_once() {
+        check_blended_once(&[(5.0, 5.0), (50.0, 20.0), (10.0, 40.0)], false, 7.0);
+    }
+
+    #[test]
+    fn beveled_bend_blended_once() {
+        // Folds back far enough to be past the miter limit
+        check_blended_once(&[(5.0, 10.0), (50.0, 20.0), (5.0, 25.0)], false, 9.0);
+    }
+
+    #[test]
+    fn closed_and_crossing_blended_once() {
+        let square = [(10.0, 10.0), (50.0, 10.0), (50.0, 50.0), (10.0, 50.0)];
+        check_blended_once(&square, true, 8.0);
+
+        let crossing = [(5.0, 5.0), (55.0, 55.0), (55.0, 5.0), (5.0, 55.0)];
+        check_blended_once(&crossing, false, 5.0);
+
+        // Tighter than the line is wide, so the inside of the outline folds
+        // over itself
+        let mut circle = Vec::new();
+        for i in 0..24 {
+            let angle = i as f64 * std::f64::consts::PI / 12.0;
+            circle.push((30.0 + 4.0 * angle.cos(), 30.0 + 4.0 * angle.sin()));
+        }
+        check_blended_once(&circle, true, 9.0);
+    }
+}
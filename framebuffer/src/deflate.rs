@@ -0,0 +1,439 @@
+/// Size of the sliding window used when searching for repeated strings
+const WINDOW_SIZE: usize = 32768;
+
+/// Number of bits used when hashing the first 3 bytes of a match
+const HASH_BITS: usize = 15;
+
+/// Number of previous positions to try before giving up on a better match
+const MAX_CHAIN: usize = 64;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Largest amount of data that fits into one stored block
+const MAX_STORED: usize = 65535;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Packs variable-length codes into bytes, least significant bit first
+struct BitWriter {
+    output: Vec<u8>,
+    current: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(output: Vec<u8>) -> BitWriter {
+        BitWriter {
+            output,
+            current: 0,
+            count: 0,
+        }
+    }
+
+    /// Writes the low bits of the value, starting from the least significant
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        self.current |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.output.push(self.current as u8);
+            self.current >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which DEFLATE stores starting from the most
+    /// significant bit
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let mut reversed = 0;
+        for i in 0..bits {
+            reversed |= ((code >> i) & 1) << (bits - 1 - i);
+        }
+        self.write_bits(reversed, bits);
+    }
+
+    /// Pads out the final byte and returns the written data
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.current as u8);
+        }
+        self.output
+    }
+}
+
+/// Writes a literal or length symbol using the fixed Huffman table from
+/// RFC 1951, section 3.2.6
+fn write_literal(bits: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + (symbol - 144), 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + (symbol - 280), 8),
+    }
+}
+
+/// Writes a back-reference as a length symbol and distance symbol, along with
+/// their extra bits
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let length_code = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(bits, 257 + length_code as u16);
+    bits.write_bits(
+        (length - LENGTH_BASE[length_code] as usize) as u32,
+        LENGTH_EXTRA[length_code] as u32,
+    );
+
+    let distance_code = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    bits.write_code(distance_code as u32, 5);
+    bits.write_bits(
+        (distance - DISTANCE_BASE[distance_code] as usize) as u32,
+        DISTANCE_EXTRA[distance_code] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = ((data[0] as usize) << 16) | ((data[1] as usize) << 8) | data[2] as usize;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) & ((1 << HASH_BITS) - 1)
+}
+
+/// Adds the string starting at the given position to the hash chains
+fn insert(data: &[u8], head: &mut [usize], prev: &mut [usize], pos: usize) {
+    if pos + MIN_MATCH <= data.len() {
+        let key = hash(&data[pos..]);
+        prev[pos % WINDOW_SIZE] = head[key];
+        head[key] = pos;
+    }
+}
+
+/// Computes the Adler-32 checksum used by the zlib trailer
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// Writes the data as a series of stored (uncompressed) DEFLATE blocks
+fn write_stored(data: &[u8], output: &mut Vec<u8>) {
+    let mut blocks = data.chunks(MAX_STORED).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0x00, 0x00, 0xff, 0xff]);
+        return;
+    }
+
+    while let Some(block) = blocks.next() {
+        // BFINAL and BTYPE = stored, padded out to the end of the byte
+        output.push(blocks.peek().is_none() as u8);
+
+        let length = block.len() as u16;
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+}
+
+/// Compresses the data into a zlib stream (RFC 1950) containing a single
+/// DEFLATE block with fixed Huffman codes. Data that the codes would make
+/// bigger, like noise, is kept in stored blocks instead.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    /*
+    Matches are found with the usual hash chain approach: head holds the most
+    recent position whose first 3 bytes hash to a given value, and prev links
+    every position in the window to the one before it with the same hash. At
+    each position we walk the chain looking for the longest match and emit
+    either a literal or a (length, distance) pair.
+     */
+    let mut output = Vec::with_capacity(data.len() / 2 + 16);
+    output.push(0x78);
+    output.push(0x01);
+
+    let mut bits = BitWriter::new(output);
+    bits.write_bits(1, 1); // BFINAL
+    bits.write_bits(1, 2); // BTYPE = fixed Huffman
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+
+        if pos + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            let mut chain = 0;
+
+            while candidate != usize::MAX && chain < MAX_CHAIN {
+                let distance = pos - candidate;
+                if distance > WINDOW_SIZE - 1 {
+                    break;
+                }
+
+                let mut length = 0;
+                while length < max_length && data[candidate + length] == data[pos + length] {
+                    length += 1;
+                }
+
+                if length > best_length {
+                    best_length = length;
+                    best_distance = distance;
+                    if length == max_length {
+                        break;
+                    }
+                }
+
+                let next = prev[candidate % WINDOW_SIZE];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            write_match(&mut bits, best_length, best_distance);
+            for offset in 0..best_length {
+                insert(data, &mut head, &mut prev, pos + offset);
+            }
+            pos += best_length;
+        } else {
+            write_literal(&mut bits, data[pos] as u16);
+            insert(data, &mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+
+    write_literal(&mut bits, 256);
+
+    let mut output = bits.finish();
+    let stored_size = data.len() + 5 * data.len().div_ceil(MAX_STORED).max(1);
+    if output.len() - 2 > stored_size {
+        output.truncate(2);
+        write_stored(data, &mut output);
+    }
+
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Reads the bits of a DEFLATE stream, least significant bit first
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let byte = self.data[self.position / 8];
+                value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        /// Reads a Huffman code, which is stored most significant bit first
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |code, _| (code << 1) | self.bits(1))
+        }
+
+        fn align(&mut self) -> usize {
+            self.position = self.position.div_ceil(8) * 8;
+            self.position / 8
+        }
+    }
+
+    /// Decodes a literal or length symbol from the fixed Huffman table
+    fn read_literal(bits: &mut BitReader) -> usize {
+        let code = bits.code(7);
+        if code <= 0x17 {
+            return 256 + code as usize;
+        }
+
+        let code = (code << 1) | bits.bits(1);
+        match code {
+            0x30..=0xbf => (code - 0x30) as usize,
+            0xc0..=0xc7 => (280 + code - 0xc0) as usize,
+            _ => (144 + ((code << 1) | bits.bits(1)) - 0x190) as usize,
+        }
+    }
+
+    /// Decompresses a zlib stream made of stored and fixed Huffman blocks,
+    /// checking its header and trailer along the way
+    pub(crate) fn zlib_decompress(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[0] & 0x0f, 8, "compression method");
+        assert_eq!(
+            u16::from_be_bytes([data[0], data[1]]) % 31,
+            0,
+            "header check"
+        );
+
+        let mut bits = BitReader { data, position: 16 };
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let last = bits.bits(1) == 1;
+            match bits.bits(2) {
+                0 => {
+                    let start = bits.align();
+                    let length = u16::from_le_bytes([data[start], data[start + 1]]);
+                    let check = u16::from_le_bytes([data[start + 2], data[start + 3]]);
+                    assert_eq!(length, !check, "stored block length");
+
+                    let start = start + 4;
+                    output.extend_from_slice(&data[start..start + length as usize]);
+                    bits.position = (start + length as usize) * 8;
+                }
+                1 => loop {
+                    let symbol = read_literal(&mut bits);
+                    if symbol < 256 {
+                        output.push(symbol as u8);
+                        continue;
+                    } else if symbol == 256 {
+                        break;
+                    }
+
+                    let code = symbol - 257;
+                    let length =
+                        LENGTH_BASE[code] as usize + bits.bits(LENGTH_EXTRA[code] as u32) as usize;
+                    let code = bits.code(5) as usize;
+                    let distance = DISTANCE_BASE[code] as usize
+                        + bits.bits(DISTANCE_EXTRA[code] as u32) as usize;
+
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                },
+                kind => panic!("unexpected block type {}", kind),
+            }
+
+            if last {
+                break;
+            }
+        }
+
+        let end = bits.align();
+        assert_eq!(data.len(), end + 4, "trailing data");
+        assert_eq!(
+            u32::from_be_bytes([data[end], data[end + 1], data[end + 2], data[end + 3]]),
+            adler32(&output)
+        );
+        output
+    }
+
+    /// Generates bytes that don't compress, using xorshift
+    pub(crate) fn noise(length: usize) -> Vec<u8> {
+        let mut state: u32 = 0x9e3779b9;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adler32_known_value() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn adler32_long_input() {
+        // Long enough to need the modulo partway through
+        let data = vec![0xff; 100_000];
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in data.iter() {
+            a = (a + byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(&data), ((b << 16) | a) as u32);
+    }
+
+    #[test]
+    fn fixed_block_round_trip() {
+        let mut data = b"abracadabra, abracadabra, abracadabra!".to_vec();
+        data.extend(std::iter::repeat_n(0, 1000));
+        data.extend_from_slice(&noise(300));
+        data.extend_from_slice(b"abracadabra");
+
+        let compressed = zlib_compress(&data);
+        assert_eq!(compressed[2] & 0x07, 0x03, "one final fixed block");
+        assert!(compressed.len() < data.len());
+        assert_eq!(zlib_decompress(&compressed), data);
+    }
+
+    #[test]
+    fn stored_block_round_trip() {
+        // Noise grows under the fixed codes, and this much of it needs more
+        // than one stored block
+        let data = noise(MAX_STORED + 1000);
+
+        let compressed = zlib_compress(&data);
+        assert_eq!(compressed[2], 0x00, "first stored block isn't final");
+        assert_eq!(compressed.len(), 2 + data.len() + 10 + 4);
+        assert_eq!(zlib_decompress(&compressed), data);
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let compressed = zlib_compress(&[]);
+        assert_eq!(compressed, [0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(zlib_decompress(&compressed), b"");
+    }
+
+    #[test]
+    fn long_distance_matches() {
+        // Repeats a block from far enough back to use the largest distance
+        // codes, and long enough to use the largest length code
+        let block = noise(600);
+        let mut data = block.clone();
+        data.extend(b"filler ".iter().cycle().take(25000));
+        data.extend_from_slice(&block);
+        data.extend(std::iter::repeat_n(b'x', 600));
+
+        let compressed = zlib_compress(&data);
+        assert_eq!(compressed[2] & 0x07, 0x03, "one final fixed block");
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(zlib_decompress(&compressed), data);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{Canvas, GraphicBuffer, Path};
+use std::f64::consts::PI;
+
+/// Moves the angle by whole turns into the range covered by an arc from start
+/// to end, which can go either way. This keeps the angles passed to gradients
+/// within that range, even when the arc crosses the point where atan2 wraps
+/// around. Angles in the gap between the ends of the arc, which the edges of
+/// a wide line can reach, are clamped to whichever end is closer.
+fn angle_within(angle: f64, start: f64, end: f64) -> f64 {
+    let (low, high) = if start <= end {
+        (start, end)
+    } else {
+        (end, start)
+    };
+
+    let angle = low + (angle - low).rem_euclid(2.0 * PI);
+    if angle <= high {
+        angle
+    } else if angle - high < low + 2.0 * PI - angle {
+        high
+    } else {
+        low
+    }
+}
+
+/// Builds the path of a pie slice, which is an arc plus two lines to its
+/// center
+fn pie_path(x: i64, y: i64, r: i64, start: f64, end: f64) -> Path {
+    let mut path = Path::new();
+    path.move_to(x as f64, y as f64);
+    path.arc(x as f64, y as f64, r as f64, start, end);
+    path.close();
+    path
+}
+
+/// Returns the angle and the scaled radius of the point relative to an
+/// ellipse, where the angle is measured from the ellipse's horizontal axis
+/// and the radius is 1 on its perimeter
+fn ellipse_position(
+    x: i64,
+    y: i64,
+    rx: i64,
+    ry: i64,
+    rotation: f64,
+    px: f64,
+    py: f64,
+) -> (f64, f64) {
+    let (sin, cos) = rotation.sin_cos();
+    let (relx, rely) = (px - x as f64, py - y as f64);
+    let (ex, ey) = (relx * cos + rely * sin, rely * cos - relx * sin);
+    let radius = ((ex / rx as f64).powi(2) + (ey / ry as f64).powi(2)).sqrt();
+    (ey.atan2(ex), radius)
+}
+
+/// Builds the path of an ellipse around the given point
+fn ellipse_path(x: i64, y: i64, rx: i64, ry: i64, rotation: f64) -> Path {
+    let mut path = Path::new();
+    path.ellipse(x as f64, y as f64, rx as f64, ry as f64, rotation);
+    path
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Draws an ellipse's perimeter around the given point using the current
+    /// stroke color. The ellipse has the horizontal and vertical radii rx and
+    /// ry before it's turned clockwise by the rotation angle.
+    pub fn stroke_ellipse(&mut self, x: i64, y: i64, rx: i64, ry: i64, rotation: f64) {
+        let stroke = self.stroke;
+        let path = ellipse_path(x, y, rx, ry, rotation);
+        self.stroke_path_with(&path, self.line_width, |_, _, _| stroke);
+    }
+
+    /// Draws an ellipse's perimeter around the given point using the given
+    /// gradient(angle), where the angle is measured from the ellipse's
+    /// horizontal axis
+    pub fn gstroke_ellipse<F>(
+        &mut self,
+        x: i64,
+        y: i64,
+        rx: i64,
+        ry: i64,
+        rotation: f64,
+        gradient: F,
+    ) where
+        F: Fn(f64) -> Element,
+    {
+        let path = ellipse_path(x, y, rx, ry, rotation);
+        self.stroke_path_with(&path, self.line_width, |px, py, _| {
+            let (angle, _) = ellipse_position(x, y, rx, ry, rotation, px, py);
+            gradient(angle)
+        });
+    }
+
+    /// Fills an ellipse around the given point using the current fill color
+    pub fn fill_ellipse(&mut self, x: i64, y: i64, rx: i64, ry: i64, rotation: f64) {
+        let fill = self.fill;
+        let path = ellipse_path(x, y, rx, ry, rotation);
+        self.fill_path_with(&path, |_, _| fill);
+    }
+
+    /// Fills an ellipse around the given point using the given
+    /// gradient(angle, radius), where the radius is scaled so that it's 1 on
+    /// the perimeter
+    pub fn gfill_ellipse<F>(&mut self, x: i64, y: i64, rx: i64, ry: i64, rotation: f64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let path = ellipse_path(x, y, rx, ry, rotation);
+        self.fill_path_with(&path, |px, py| {
+            let (angle, radius) = ellipse_position(x, y, rx, ry, rotation, px, py);
+            gradient(angle, radius.min(1.0))
+        });
+    }
+
+    /// Draws part of a circle's perimeter around the given point, from the
+    /// start angle to the end angle, using the current stroke color. Angles
+    /// increase clockwise, and the arc goes counter-clockwise when the end is
+    /// less than the start.
+    pub fn stroke_arc(&mut self, x: i64, y: i64, r: i64, start: f64, end: f64) {
+        let stroke = self.stroke;
+        self.gstroke_arc(x, y, r, start, end, |_| stroke);
+    }
+
+    /// Draws part of a circle's perimeter around the given point using the
+    /// given gradient(angle), where the angle runs from start to end
+    pub fn gstroke_arc<F>(&mut self, x: i64, y: i64, r: i64, start: f64, end: f64, gradient: F)
+    where
+        F: Fn(f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.arc(x as f64, y as f64, r as f64, start, end);
+
+        self.stroke_path_with(&path, self.line_width, |px, py, _| {
+            let angle = (py - y as f64).atan2(px - x as f64);
+            gradient(angle_within(angle, start, end))
+        });
+    }
+
+    /// Draws the outline of a pie slice, with the arc running from the start
+    /// angle to the end angle, using the current stroke color
+    pub fn stroke_pie(&mut self, x: i64, y: i64, r: i64, start: f64, end: f64) {
+        let stroke = self.stroke;
+        let path = pie_path(x, y, r, start, end);
+        self.stroke_path_with(&path, self.line_width, |_, _, _| stroke);
+    }
+
+    /// Fills a pie slice, with the arc running from the start angle to the end
+    /// angle, using the current fill color
+    pub fn fill_pie(&mut self, x: i64, y: i64, r: i64, start: f64, end: f64) {
+        let fill = self.fill;
+        self.gfill_pie(x, y, r, start, end, |_, _| fill);
+    }
+
+    /// Fills a pie slice using the given gradient(angle, radius), where the
+    /// angle runs from start to end and the radius is 1 on the arc
+    pub fn gfill_pie<F>(&mut self, x: i64, y: i64, r: i64, start: f64, end: f64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let path = pie_path(x, y, r, start, end);
+        self.fill_path_with(&path, |px, py| {
+            let (relx, rely) = (px - x as f64, py - y as f64);
+            let angle = angle_within(rely.atan2(relx), start, end);
+            let radius = (relx * relx + rely * rely).sqrt() / r as f64;
+            gradient(angle, radius.min(1.0))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StencilBuffer;
+
+    /// Draws an arc around (30, 30) where each pixel's value is how far its
+    /// gradient angle is along the arc, from 1 at one end to 251 at the
+    /// other, and checks every pixel against its own angle
+    fn check_arc(start: f64, end: f64, width: f64) {
+        let (low, high) = (start.min(end), start.max(end));
+        let value = |angle: f64| ((angle - low) / (high - low) * 250.0).round() as u8 + 1;
+
+        let mut canvas = Canvas::new(StencilBuffer::new(60, 60), 0, 0);
+        canvas.set_line_width(width);
+        canvas.gstroke_arc(30, 30, 20, start, end, |angle| {
+            assert!(
+                angle >= low && angle <= high,
+                "{} outside of the arc",
+                angle
+            );
+            value(angle)
+        });
+
+        let mut drawn = 0;
+        for py in 0..60 {
+            for px in 0..60 {
+                let actual = canvas.get_point(px, py).unwrap();
+                if actual == 0 {
+                    continue;
+                }
+
+                // Pixels past the ends have to take the color of the nearer
+                // end, rather than jumping to the far one
+                drawn += 1;
+                let angle = ((py - 30) as f64).atan2((px - 30) as f64);
+                let angle = low + (angle - low).rem_euclid(2.0 * PI);
+                let expected = if angle <= high {
+                    value(angle)
+                } else if angle - high < low + 2.0 * PI - angle {
+                    251
+                } else {
+                    1
+                };
+                assert_eq!(actual, expected, "({}, {}) at {}", px, py, angle);
+            }
+        }
+        assert!(drawn > 100);
+    }
+
+    #[test]
+    fn arc_gradient_just_under_a_full_circle() {
+        let sweep = 2.0 * PI - 0.05;
+        for &width in [1.0, 6.0].iter() {
+            for &start in [0.0, 0.3, -2.0, PI].iter() {
+                check_arc(start, start + sweep, width);
+                check_arc(start + sweep, start, width);
+            }
+        }
+    }
+
+    #[test]
+    fn arc_gradient_across_the_wrap() {
+        // An arc crossing the angle where atan2 jumps from PI to -PI
+        check_arc(PI - 0.5, PI + 0.5, 6.0);
+        check_arc(0.5, -0.5, 6.0);
+    }
+
+    #[test]
+    fn pie_gradient_stays_on_the_arc() {
+        let (start, end) = (1.0, 1.0 + 2.0 * PI - 0.05);
+        let mut canvas = Canvas::new(StencilBuffer::new(60, 60), 0, 0);
+        canvas.gfill_pie(30, 30, 20, start, end, |angle, radius| {
+            assert!(
+                angle >= start && angle <= end,
+                "{} outside of the arc",
+                angle
+            );
+            assert!((0.0..=1.0).contains(&radius));
+            1
+        });
+        assert_eq!(canvas.get_point(30, 20), Some(1));
+    }
+}
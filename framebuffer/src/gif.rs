@@ -0,0 +1,723 @@
+use crate::quantize::{self, PaletteMap};
+use crate::write_all;
+use crate::{Color, FrameBuffer, FrameSink};
+use std::collections::HashMap;
+use std::io;
+
+/// The largest number of colors in a palette, leaving one of the 256 indexes
+/// free for transparency
+const MAX_COLORS: usize = 255;
+
+/// How the colors for each frame of a GIF are chosen
+#[derive(Clone, PartialEq)]
+pub enum GifPalette {
+    /// Each frame gets its own palette, built from the pixels that changed
+    /// since the previous frame
+    PerFrame,
+    /// A single palette is built from the first frame and shared by every
+    /// later frame
+    Global,
+    /// The given colors (from 1 up to 255 of them) are shared by every frame
+    Fixed(Vec<Color>),
+}
+
+/// Packs LZW codes into bytes, least significant bit first, and splits them
+/// into GIF data sub-blocks
+struct CodeWriter {
+    output: Vec<u8>,
+    current: u32,
+    count: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, bits: u32) {
+        self.current |= (code as u32) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.output.push(self.current as u8);
+            self.current >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.current as u8);
+        }
+
+        let mut blocks = Vec::with_capacity(self.output.len() + self.output.len() / 255 + 2);
+        for chunk in self.output.chunks(255) {
+            blocks.push(chunk.len() as u8);
+            blocks.extend_from_slice(chunk);
+        }
+        blocks.push(0);
+        blocks
+    }
+}
+
+/// Compresses palette indexes using the variable-length LZW scheme from the
+/// GIF89a specification, appendix F
+fn lzw_compress(indexes: &[u8], min_code_size: u32) -> Vec<u8> {
+    /*
+    This follows the same conventions as giflib's encoder: the code size grows
+    once the next free code no longer fits in the current size, and when all
+    4096 codes are used up a clear code is written and the table is rebuilt
+    from scratch.
+     */
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut codes = CodeWriter {
+        output: Vec::new(),
+        current: 0,
+        count: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end + 1;
+
+    codes.write(clear, code_size);
+
+    let mut prefix = match indexes.first() {
+        Some(&index) => index as u16,
+        None => {
+            codes.write(end, code_size);
+            return codes.finish();
+        }
+    };
+
+    for &index in &indexes[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        codes.write(prefix, code_size);
+        if next_code >= (1 << code_size) && code_size < 12 {
+            code_size += 1;
+        }
+
+        if next_code >= 4095 {
+            codes.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end + 1;
+        } else {
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    codes.write(prefix, code_size);
+    if next_code >= (1 << code_size) && code_size < 12 {
+        code_size += 1;
+    }
+    codes.write(end, code_size);
+    codes.finish()
+}
+
+/// Returns the number of bits needed to index a color table with the given
+/// number of entries. GIF color tables always have a power of two size of
+/// at least 2.
+fn table_bits(entries: usize) -> u32 {
+    let mut bits = 1;
+    while (1 << bits) < entries {
+        bits += 1;
+    }
+    bits
+}
+
+/// Builds a color table padded out to the size implied by table_bits
+fn color_table(palette: &[Color], bits: u32) -> Vec<u8> {
+    let mut table = Vec::with_capacity(3 << bits);
+    for color in palette {
+        table.extend_from_slice(&[color.r, color.g, color.b]);
+    }
+    table.resize(3 << bits, 0);
+    table
+}
+
+/// Writes FrameBuffers as an animated GIF. After the first frame, pixels that
+/// haven't changed are left transparent and each frame only covers the region
+/// that did change, which keeps mostly-static animations small. finish has to
+/// be called after the last frame to end the file.
+pub struct GifWriter<W: io::Write> {
+    output: W,
+    palette: GifPalette,
+    delay: u16,
+    repeat: Option<u16>,
+    shared_palette: Option<Vec<Color>>,
+    previous: Option<Vec<u8>>,
+    size: Option<(u32, u32)>,
+    finished: bool,
+}
+
+impl<W: io::Write> GifWriter<W> {
+    /// Creates a writer where each frame is shown for the given number of
+    /// hundredths of a second. A fixed palette must have at least one color.
+    pub fn new(output: W, delay: u16, palette: GifPalette) -> io::Result<GifWriter<W>> {
+        if let GifPalette::Fixed(colors) = &palette {
+            if colors.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "fixed GIF palette has no colors",
+                ));
+            }
+        }
+
+        Ok(GifWriter {
+            output,
+            palette,
+            delay,
+            repeat: Some(0),
+            shared_palette: None,
+            previous: None,
+            size: None,
+            finished: false,
+        })
+    }
+
+    /// Sets the delay for the frames written after this call, in hundredths
+    /// of a second
+    pub fn set_delay(&mut self, delay: u16) {
+        self.delay = delay;
+    }
+
+    /// Sets how many times the animation plays after the first time through:
+    /// None plays it once, Some(0) loops forever. This must be done before the
+    /// first frame is written, since it's part of the file header.
+    pub fn set_repeat(&mut self, repeat: Option<u16>) {
+        self.repeat = repeat;
+    }
+
+    fn write_header(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        if frame.width > 65535 || frame.height > 65535 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF frames must be at most 65535 pixels on each side",
+            ));
+        }
+
+        self.shared_palette = match &self.palette {
+            GifPalette::PerFrame => None,
+            GifPalette::Global => Some(quantize::median_cut(
+                &quantize::histogram(&frame.pixels),
+                MAX_COLORS,
+            )),
+            GifPalette::Fixed(colors) => Some(colors.iter().take(MAX_COLORS).copied().collect()),
+        };
+
+        write_all(&mut self.output, b"GIF89a")?;
+
+        let mut screen = Vec::with_capacity(7);
+        screen.extend_from_slice(&(frame.width as u16).to_le_bytes());
+        screen.extend_from_slice(&(frame.height as u16).to_le_bytes());
+        match &self.shared_palette {
+            Some(palette) => {
+                // The transparent index comes right after the palette colors
+                let bits = table_bits(palette.len() + 1);
+                screen.push(0x80 | 0x70 | (bits - 1) as u8);
+                screen.push(0); // Background color index
+                screen.push(0); // Pixel aspect ratio
+                screen.extend_from_slice(&color_table(palette, bits));
+            }
+            None => {
+                screen.push(0x70);
+                screen.push(0);
+                screen.push(0);
+            }
+        }
+        write_all(&mut self.output, &screen)?;
+
+        if let Some(repeat) = self.repeat {
+            let mut extension = vec![0x21, 0xff, 0x0b];
+            extension.extend_from_slice(b"NETSCAPE2.0");
+            extension.extend_from_slice(&[0x03, 0x01]);
+            extension.extend_from_slice(&repeat.to_le_bytes());
+            extension.push(0);
+            write_all(&mut self.output, &extension)?;
+        }
+
+        self.size = Some((frame.width, frame.height));
+        Ok(())
+    }
+
+    /// Adds a frame to the animation. The file header is written along with
+    /// the first frame, and every later frame must have the same size.
+    pub fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF has already been finished",
+            ));
+        }
+
+        match self.size {
+            None => self.write_header(frame)?,
+            Some((width, height)) => {
+                if width != frame.width || height != frame.height {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "frame size differs from the GIF header",
+                    ));
+                }
+            }
+        }
+
+        let width = frame.width as usize;
+        let changed = |x: usize, y: usize| match &self.previous {
+            None => true,
+            Some(previous) => {
+                let offset = (y * width + x) * 3;
+                previous[offset..offset + 3] != frame.pixels[offset..offset + 3]
+            }
+        };
+
+        // Find the smallest rectangle covering every changed pixel. If nothing
+        // changed a single transparent pixel is still written, so that the
+        // frame's delay isn't lost.
+        let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+        for y in 0..frame.height as usize {
+            for x in 0..width {
+                if changed(x, y) {
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x + 1);
+                    bottom = bottom.max(y + 1);
+                }
+            }
+        }
+
+        if left == usize::MAX {
+            left = 0;
+            top = 0;
+            right = 1;
+            bottom = 1;
+        }
+
+        let mut region = Vec::with_capacity((right - left) * (bottom - top) * 3);
+        let mut mask = Vec::with_capacity((right - left) * (bottom - top));
+        for y in top..bottom {
+            for x in left..right {
+                let offset = (y * width + x) * 3;
+                let is_changed = changed(x, y);
+                mask.push(is_changed);
+                if is_changed {
+                    region.extend_from_slice(&frame.pixels[offset..offset + 3]);
+                }
+            }
+        }
+
+        let local_palette;
+        let palette = match &self.shared_palette {
+            Some(palette) => palette,
+            None => {
+                local_palette = quantize::median_cut(&quantize::histogram(&region), MAX_COLORS);
+                &local_palette
+            }
+        };
+
+        let transparent = palette.len() as u8;
+        let mut mapping = PaletteMap::new(palette);
+        let mut colors = region.chunks(3);
+        let indexes: Vec<u8> = mask
+            .iter()
+            .map(|&is_changed| {
+                if is_changed {
+                    let color = colors.next().unwrap();
+                    mapping.index([color[0], color[1], color[2]])
+                } else {
+                    transparent
+                }
+            })
+            .collect();
+
+        let bits = table_bits(palette.len() + 1);
+
+        // Graphic control extension: leave the frame in place when the next
+        // one is drawn (disposal method 1) with the transparent index enabled
+        let mut block = vec![0x21, 0xf9, 0x04, (1 << 2) | 1];
+        block.extend_from_slice(&self.delay.to_le_bytes());
+        block.push(transparent);
+        block.push(0);
+
+        block.push(0x2c);
+        block.extend_from_slice(&(left as u16).to_le_bytes());
+        block.extend_from_slice(&(top as u16).to_le_bytes());
+        block.extend_from_slice(&((right - left) as u16).to_le_bytes());
+        block.extend_from_slice(&((bottom - top) as u16).to_le_bytes());
+        if self.shared_palette.is_some() {
+            block.push(0);
+        } else {
+            block.push(0x80 | (bits - 1) as u8);
+            block.extend_from_slice(&color_table(palette, bits));
+        }
+
+        let min_code_size = bits.max(2);
+        block.push(min_code_size as u8);
+        block.extend_from_slice(&lzw_compress(&indexes, min_code_size));
+        write_all(&mut self.output, &block)?;
+        self.output.flush()?;
+
+        self.previous = Some(frame.pixels.clone());
+        Ok(())
+    }
+
+    /// Writes the GIF trailer, without which the file is incomplete. This must
+    /// be called after the last frame: dropping the writer also writes the
+    /// trailer, but has no way to report it if that fails.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished || self.size.is_none() {
+            return Ok(());
+        }
+
+        self.finished = true;
+        write_all(&mut self.output, &[0x3b])?;
+        self.output.flush()
+    }
+}
+
+impl<W: io::Write> Drop for GifWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl<W: io::Write> FrameSink for GifWriter<W> {
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        GifWriter::write_frame(self, frame)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        GifWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Canvas, GraphicBuffer};
+
+    /// Decompresses GIF LZW data that has already been taken out of its
+    /// sub-blocks, returning the indexes and how many clear codes were seen
+    fn lzw_decompress(data: &[u8], min_code_size: u32) -> (Vec<u8>, usize) {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            let mut table: Vec<Vec<u8>> = (0..clear).map(|i| vec![i as u8]).collect();
+            table.extend([Vec::new(), Vec::new()]);
+            table
+        };
+
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut output, mut clears) = (Vec::new(), 0);
+        let mut position = 0;
+        loop {
+            let mut code = 0;
+            for i in 0..code_size as usize {
+                let bit = (data[(position + i) / 8] >> ((position + i) % 8)) & 1;
+                code |= (bit as usize) << i;
+            }
+            position += code_size as usize;
+
+            if code == clear {
+                table = reset();
+                code_size = min_code_size + 1;
+                previous = None;
+                clears += 1;
+                continue;
+            } else if code == end {
+                break;
+            }
+
+            let entry = if code < table.len() {
+                table[code].clone()
+            } else {
+                assert_eq!(code, table.len(), "code from the future");
+                let mut entry = previous.clone().unwrap();
+                entry.push(entry[0]);
+                entry
+            };
+
+            output.extend_from_slice(&entry);
+            if let Some(mut previous) = previous {
+                previous.push(entry[0]);
+                table.push(previous);
+            }
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            previous = Some(entry);
+        }
+
+        assert_eq!(position.div_ceil(8), data.len(), "data after the end code");
+        (output, clears)
+    }
+
+    /// Joins GIF data sub-blocks, returning the data and what follows them
+    fn sub_blocks(data: &[u8]) -> (Vec<u8>, &[u8]) {
+        let (mut joined, mut rest) = (Vec::new(), data);
+        while rest[0] != 0 {
+            let length = rest[0] as usize;
+            joined.extend_from_slice(&rest[1..1 + length]);
+            rest = &rest[1 + length..];
+        }
+        (joined, &rest[1..])
+    }
+
+    /// One image from a decoded GIF
+    struct Frame {
+        /// The whole screen after the image was drawn
+        pixels: Vec<[u8; 3]>,
+        /// The (left, top, width, height) of the image on the screen
+        region: (usize, usize, usize, usize),
+        delay: u16,
+        clears: usize,
+    }
+
+    /// Decodes a GIF written by GifWriter, drawing each image over the last
+    fn decode(data: &[u8]) -> (Option<u16>, Vec<Frame>) {
+        assert_eq!(&data[..6], b"GIF89a");
+        let width = u16::from_le_bytes([data[6], data[7]]) as usize;
+        let height = u16::from_le_bytes([data[8], data[9]]) as usize;
+        let table_size = |flags: u8| 3 << ((flags & 0x07) + 1);
+
+        let mut rest = &data[13..];
+        let mut global = Vec::new();
+        if data[10] & 0x80 != 0 {
+            global = rest[..table_size(data[10])].to_vec();
+            rest = &rest[table_size(data[10])..];
+        }
+
+        let mut screen = vec![[0; 3]; width * height];
+        let (mut repeat, mut frames) = (None, Vec::new());
+        let (mut delay, mut transparent) = (0, None);
+        loop {
+            match rest[0] {
+                0x21 if rest[1] == 0xf9 => {
+                    assert_eq!(rest[3] & 0x1c, 1 << 2, "disposal method 1");
+                    delay = u16::from_le_bytes([rest[4], rest[5]]);
+                    transparent = if rest[3] & 1 == 1 {
+                        Some(rest[6])
+                    } else {
+                        None
+                    };
+                    rest = &rest[8..];
+                }
+                0x21 => {
+                    let (body, after) = sub_blocks(&rest[2..]);
+                    if body.starts_with(b"NETSCAPE2.0") {
+                        repeat = Some(u16::from_le_bytes([body[12], body[13]]));
+                    }
+                    rest = after;
+                }
+                0x2c => {
+                    let field = |i: usize| u16::from_le_bytes([rest[i], rest[i + 1]]) as usize;
+                    let region = (field(1), field(3), field(5), field(7));
+                    let flags = rest[9];
+                    rest = &rest[10..];
+
+                    let mut palette = &global[..];
+                    if flags & 0x80 != 0 {
+                        palette = &rest[..table_size(flags)];
+                        rest = &rest[table_size(flags)..];
+                    }
+
+                    let min_code_size = rest[0] as u32;
+                    let (compressed, after) = sub_blocks(&rest[1..]);
+                    rest = after;
+                    let (indexes, clears) = lzw_decompress(&compressed, min_code_size);
+
+                    let (left, top, region_width, region_height) = region;
+                    assert_eq!(indexes.len(), region_width * region_height);
+                    for (i, &index) in indexes.iter().enumerate() {
+                        if Some(index) != transparent {
+                            let index = index as usize * 3;
+                            let (x, y) = (left + i % region_width, top + i / region_width);
+                            screen[y * width + x] =
+                                [palette[index], palette[index + 1], palette[index + 2]];
+                        }
+                    }
+
+                    frames.push(Frame {
+                        pixels: screen.clone(),
+                        region,
+                        delay,
+                        clears,
+                    });
+                }
+                0x3b => {
+                    assert_eq!(rest.len(), 1, "data after the trailer");
+                    return (repeat, frames);
+                }
+                other => panic!("unexpected block {:#x}", other),
+            }
+        }
+    }
+
+    fn pixels(frame: &FrameBuffer) -> Vec<[u8; 3]> {
+        frame
+            .pixels
+            .chunks(3)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect()
+    }
+
+    /// Generates numbers that look random, using xorshift
+    fn noise(count: usize, modulo: u32) -> Vec<u32> {
+        let mut state: u32 = 0x2545f491;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state % modulo
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        for min_code_size in 2..=8 {
+            let colors = 1 << min_code_size;
+            let mut indexes: Vec<u8> = noise(30000, colors).iter().map(|&i| i as u8).collect();
+            indexes.extend(std::iter::repeat_n(1, 5000));
+            indexes.extend((0..5000).map(|i| (i / 7 % colors) as u8));
+
+            let compressed = lzw_compress(&indexes, min_code_size);
+            let (data, rest) = sub_blocks(&compressed);
+            assert!(rest.is_empty());
+
+            let (decompressed, clears) = lzw_decompress(&data, min_code_size);
+            assert_eq!(decompressed, indexes, "min code size {}", min_code_size);
+            assert!(clears > 1, "no reset at min code size {}", min_code_size);
+        }
+    }
+
+    #[test]
+    fn lzw_short_inputs() {
+        for indexes in [&[][..], &[3], &[0, 0], &[1, 2, 1, 2, 1, 2, 1]] {
+            let (data, _) = sub_blocks(&lzw_compress(indexes, 2));
+            assert_eq!(lzw_decompress(&data, 2), (indexes.to_vec(), 1));
+        }
+    }
+
+    #[test]
+    fn noise_frame_round_trip() {
+        // 200 colors fit into the palette exactly, and noise fills up the
+        // code table many times over
+        let colors: Vec<Color> = (0..200u32)
+            .map(|i| Color::rgb(i as u8, (i * 7) as u8, (i * 13) as u8))
+            .collect();
+        let mut frame = FrameBuffer::new(160, 120);
+        for (i, index) in noise(160 * 120, 200).into_iter().enumerate() {
+            frame.put_point(i as i64 % 160, i as i64 / 160, colors[index as usize]);
+        }
+
+        let mut output = Vec::new();
+        let mut writer = GifWriter::new(&mut output, 4, GifPalette::PerFrame).unwrap();
+        writer.write_frame(&frame).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let (repeat, frames) = decode(&output);
+        assert_eq!(repeat, Some(0));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].region, (0, 0, 160, 120));
+        assert!(frames[0].clears > 2);
+        assert_eq!(frames[0].pixels, pixels(&frame));
+    }
+
+    #[test]
+    fn delta_frames() {
+        let mut canvas = Canvas::new(FrameBuffer::new(40, 30), Color::white(), Color::black());
+        canvas.fill();
+        canvas.put_point(39, 0, Color::black());
+        for x in 0..40 {
+            canvas.put_point(x, x % 30, Color::rgb(200, 0, 0));
+        }
+
+        let mut output = Vec::new();
+        let mut writer = GifWriter::new(&mut output, 10, GifPalette::Global).unwrap();
+        writer.set_repeat(Some(3));
+        let mut expected = Vec::new();
+
+        writer.write_frame(canvas.buffer()).unwrap();
+        expected.push(pixels(canvas.buffer()));
+
+        // The palette comes from the first frame, which has every color used
+        // here. Only the changed rectangle is written, and the pixels inside of it
+        // that didn't change are left transparent
+        canvas.set_fill(Color::black());
+        canvas.fill_rect(5, 8, 10, 4);
+        canvas.put_point(20, 25, Color::rgb(200, 0, 0));
+        writer.set_delay(25);
+        writer.write_frame(canvas.buffer()).unwrap();
+        expected.push(pixels(canvas.buffer()));
+
+        // Nothing changed, so a single transparent pixel is written
+        writer.write_frame(canvas.buffer()).unwrap();
+        expected.push(pixels(canvas.buffer()));
+
+        writer.finish().unwrap();
+        let error = writer.write_frame(canvas.buffer()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        drop(writer);
+
+        let (repeat, frames) = decode(&output);
+        assert_eq!(repeat, Some(3));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].region, (0, 0, 40, 30));
+        assert_eq!(frames[1].region, (5, 8, 16, 18));
+        assert_eq!(frames[2].region, (0, 0, 1, 1));
+        assert_eq!(
+            frames.iter().map(|frame| frame.delay).collect::<Vec<_>>(),
+            [10, 25, 25]
+        );
+        for (frame, expected) in frames.iter().zip(expected.iter()) {
+            assert_eq!(&frame.pixels, expected);
+        }
+    }
+
+    #[test]
+    fn fixed_palette_maps_to_nearest() {
+        let palette = vec![Color::black(), Color::white(), Color::rgb(255, 0, 0)];
+        let mut frame = FrameBuffer::new(3, 1);
+        frame.put_point(0, 0, Color::rgb(20, 10, 10));
+        frame.put_point(1, 0, Color::rgb(240, 250, 245));
+        frame.put_point(2, 0, Color::rgb(230, 30, 20));
+
+        let mut output = Vec::new();
+        let mut writer = GifWriter::new(&mut output, 0, GifPalette::Fixed(palette)).unwrap();
+        writer.set_repeat(None);
+        writer.write_frame(&frame).unwrap();
+        drop(writer);
+
+        // Dropping the writer finishes the file
+        let (repeat, frames) = decode(&output);
+        assert_eq!(repeat, None);
+        assert_eq!(frames[0].pixels, [[0, 0, 0], [255, 255, 255], [255, 0, 0]]);
+    }
+
+    #[test]
+    fn empty_fixed_palette() {
+        let mut output = Vec::new();
+        let error = GifWriter::new(&mut output, 0, GifPalette::Fixed(Vec::new()))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn frame_size_must_match() {
+        let mut writer = GifWriter::new(Vec::new(), 0, GifPalette::PerFrame).unwrap();
+        writer.write_frame(&FrameBuffer::new(4, 4)).unwrap();
+        let error = writer.write_frame(&FrameBuffer::new(4, 5)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
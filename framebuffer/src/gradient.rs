@@ -0,0 +1,291 @@
+use crate::{Color, ColorSpace};
+use std::f64::consts::PI;
+
+/// Decides what color a gradient has past the ends of its stops
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Spread {
+    /// The colors at the ends carry on forever
+    Pad,
+    /// The stops start over again from the beginning
+    Repeat,
+    /// The stops go back and forth, so that each copy mirrors the last
+    Reflect,
+}
+
+impl Spread {
+    /// Moves a ratio anywhere along the gradient into the range 0 to 1
+    fn apply(self, ratio: f64) -> f64 {
+        match self {
+            Spread::Pad => ratio.clamp(0.0, 1.0),
+            Spread::Repeat => ratio.rem_euclid(1.0),
+            Spread::Reflect => {
+                let ratio = ratio.rem_euclid(2.0);
+                if ratio > 1.0 {
+                    2.0 - ratio
+                } else {
+                    ratio
+                }
+            }
+        }
+    }
+}
+
+/// The shape of a gradient, which decides how far along it each point is
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Shape {
+    Linear { x: f64, y: f64, x2: f64, y2: f64 },
+    Radial { x: f64, y: f64, r: f64 },
+    Conic { x: f64, y: f64, angle: f64 },
+}
+
+/// A set of colors spread out over an area. Gradients are laid out in the
+/// same coordinates used by the gradient functions that the Canvas methods
+/// take, where (0, 0) and (1, 1) are the corners of the shape being drawn.
+#[derive(Clone, PartialEq)]
+pub struct Gradient {
+    shape: Shape,
+    stops: Vec<(f64, Color)>,
+    spread: Spread,
+    space: ColorSpace,
+}
+
+impl Gradient {
+    /// Creates a gradient that runs in a straight line from the first point
+    /// to the second
+    pub fn linear(x: f64, y: f64, x2: f64, y2: f64) -> Gradient {
+        Gradient::new(Shape::Linear { x, y, x2, y2 })
+    }
+
+    /// Creates a gradient that runs outward from the center to the edge of a
+    /// circle with the given radius
+    pub fn radial(x: f64, y: f64, r: f64) -> Gradient {
+        Gradient::new(Shape::Radial { x, y, r })
+    }
+
+    /// Creates a gradient that sweeps clockwise around the center once,
+    /// starting at the given angle
+    pub fn conic(x: f64, y: f64, angle: f64) -> Gradient {
+        Gradient::new(Shape::Conic { x, y, angle })
+    }
+
+    fn new(shape: Shape) -> Gradient {
+        Gradient {
+            shape,
+            stops: Vec::new(),
+            spread: Spread::Pad,
+            space: ColorSpace::Srgb,
+        }
+    }
+
+    /// Adds a color at the given offset, which runs from 0 at the start of
+    /// the gradient to 1 at the end. Stops at the same offset make a hard
+    /// edge between them, with the one added first on the near side.
+    pub fn add_stop(&mut self, offset: f64, color: Color) {
+        let index = self.stops.partition_point(|&(other, _)| other <= offset);
+        self.stops.insert(index, (offset, color));
+    }
+
+    /// Sets what happens past the ends of the gradient
+    pub fn set_spread(&mut self, spread: Spread) {
+        self.spread = spread;
+    }
+
+    /// Sets the space that the colors are mixed in between stops
+    pub fn set_color_space(&mut self, space: ColorSpace) {
+        self.space = space;
+    }
+
+    /// Gets the color at the given ratio along the gradient, ignoring its
+    /// shape. Gradients without any stops are transparent.
+    pub fn sample(&self, ratio: f64) -> Color {
+        let ratio = self.spread.apply(ratio);
+        let after = self.stops.partition_point(|&(offset, _)| offset <= ratio);
+
+        if self.stops.is_empty() {
+            Color::rgba(0, 0, 0, 0)
+        } else if after == 0 {
+            self.stops[0].1
+        } else if after == self.stops.len() {
+            self.stops[after - 1].1
+        } else {
+            let (start, from) = self.stops[after - 1];
+            let (end, to) = self.stops[after];
+            from.interpolate(to, (ratio - start) / (end - start), self.space)
+        }
+    }
+
+    /// Gets the color at the given point
+    pub fn color_at(&self, px: f64, py: f64) -> Color {
+        let ratio = match self.shape {
+            Shape::Linear { x, y, x2, y2 } => {
+                let (dx, dy) = (x2 - x, y2 - y);
+                let length_squared = dx * dx + dy * dy;
+                if length_squared == 0.0 {
+                    0.0
+                } else {
+                    ((px - x) * dx + (py - y) * dy) / length_squared
+                }
+            }
+            Shape::Radial { x, y, r } => {
+                if r == 0.0 {
+                    1.0
+                } else {
+                    (px - x).hypot(py - y) / r
+                }
+            }
+            Shape::Conic { x, y, angle } => {
+                ((py - y).atan2(px - x) - angle).rem_euclid(2.0 * PI) / (2.0 * PI)
+            }
+        };
+
+        self.sample(ratio)
+    }
+
+    /// Adapts the gradient for methods that take a gradient(ratio), like
+    /// gstroke_line, by sampling it directly
+    pub fn by_ratio(&self) -> impl Fn(f64) -> Color + '_ {
+        move |ratio| self.sample(ratio)
+    }
+
+    /// Adapts the gradient for methods that take a gradient(xratio, yratio),
+    /// like gfill_rect
+    pub fn by_position(&self) -> impl Fn(f64, f64) -> Color + '_ {
+        move |x, y| self.color_at(x, y)
+    }
+
+    /// Adapts the gradient for methods that take a gradient(angle, radius),
+    /// like gfill_circle. The circle fits into the unit square, so its center
+    /// is at (0.5, 0.5).
+    pub fn by_polar(&self) -> impl Fn(f64, f64) -> Color + '_ {
+        move |angle, radius| {
+            let (sin, cos) = angle.sin_cos();
+            self.color_at(0.5 + 0.5 * radius * cos, 0.5 + 0.5 * radius * sin)
+        }
+    }
+
+    /// Adapts the gradient for methods that take a gradient(angle), like
+    /// gstroke_circle, by using the color on the edge of the circle used by
+    /// by_polar
+    pub fn by_angle(&self) -> impl Fn(f64) -> Color + '_ {
+        let polar = self.by_polar();
+        move |angle| polar(angle, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets all four channels of the color
+    fn channels(color: Color) -> [u8; 4] {
+        [color.r, color.g, color.b, color.alpha]
+    }
+
+    /// A linear gradient from black to white along the x axis
+    fn black_to_white() -> Gradient {
+        let mut gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0);
+        gradient.add_stop(0.0, Color::black());
+        gradient.add_stop(1.0, Color::white());
+        gradient
+    }
+
+    #[test]
+    fn spread_past_the_ends() {
+        let expected = [
+            (Spread::Pad, [0.0, 1.0, 1.0, 1.0, 0.5]),
+            (Spread::Repeat, [0.75, 0.25, 0.5, 0.0, 0.5]),
+            (Spread::Reflect, [0.25, 0.75, 0.5, 1.0, 0.5]),
+        ];
+        for &(spread, ratios) in expected.iter() {
+            for (&ratio, &expected) in [-0.25, 1.25, 2.5, 1.0, 0.5].iter().zip(ratios.iter()) {
+                assert_eq!(spread.apply(ratio), expected, "{:?} at {}", spread, ratio);
+            }
+        }
+    }
+
+    #[test]
+    fn spread_on_samples() {
+        let mut gradient = black_to_white();
+        assert_eq!(channels(gradient.sample(-0.25)), [0, 0, 0, 255]);
+        assert_eq!(channels(gradient.sample(1.25)), [255, 255, 255, 255]);
+
+        gradient.set_spread(Spread::Repeat);
+        assert_eq!(channels(gradient.sample(1.25)), [64, 64, 64, 255]);
+
+        gradient.set_spread(Spread::Reflect);
+        assert_eq!(channels(gradient.sample(1.25)), [191, 191, 191, 255]);
+        assert_eq!(channels(gradient.sample(-0.25)), [64, 64, 64, 255]);
+    }
+
+    #[test]
+    fn mixes_between_stops() {
+        let gradient = black_to_white();
+        assert_eq!(channels(gradient.sample(0.0)), [0, 0, 0, 255]);
+        assert_eq!(channels(gradient.sample(0.5)), [128, 128, 128, 255]);
+        assert_eq!(channels(gradient.sample(1.0)), [255, 255, 255, 255]);
+
+        // Stops are kept in order however they're added
+        let mut gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0);
+        gradient.add_stop(1.0, Color::rgb(0, 0, 200));
+        gradient.add_stop(0.0, Color::rgb(200, 0, 0));
+        assert_eq!(channels(gradient.sample(0.25)), [150, 0, 50, 255]);
+    }
+
+    #[test]
+    fn hard_edge() {
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let mut gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0);
+        gradient.add_stop(0.0, red);
+        gradient.add_stop(0.5, red);
+        gradient.add_stop(0.5, blue);
+        gradient.add_stop(1.0, blue);
+
+        assert_eq!(channels(gradient.sample(0.25)), [255, 0, 0, 255]);
+        assert_eq!(channels(gradient.sample(0.4999)), [255, 0, 0, 255]);
+        assert_eq!(channels(gradient.sample(0.5)), [0, 0, 255, 255]);
+        assert_eq!(channels(gradient.sample(0.75)), [0, 0, 255, 255]);
+
+        // The first stop added at an offset is on the near side of the edge
+        let mut gradient = Gradient::linear(0.0, 0.0, 1.0, 0.0);
+        gradient.add_stop(0.5, red);
+        gradient.add_stop(0.5, blue);
+        assert_eq!(channels(gradient.sample(0.25)), [255, 0, 0, 255]);
+        assert_eq!(channels(gradient.sample(0.75)), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn no_stops() {
+        let gradient = Gradient::radial(0.5, 0.5, 0.5);
+        for &ratio in [-1.0, 0.0, 0.5, 1.0, 3.0].iter() {
+            assert_eq!(channels(gradient.sample(ratio)), [0, 0, 0, 0]);
+        }
+        assert_eq!(channels(gradient.color_at(0.2, 0.7)), [0, 0, 0, 0]);
+
+        let mut gradient = Gradient::conic(0.0, 0.0, 0.0);
+        gradient.add_stop(0.3, Color::rgb(1, 2, 3));
+        for &ratio in [-1.0, 0.0, 0.5, 1.0, 3.0].iter() {
+            assert_eq!(channels(gradient.sample(ratio)), [1, 2, 3, 255]);
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let mut gradient = black_to_white();
+        assert_eq!(gradient.color_at(0.5, 7.0).r, 128);
+
+        gradient = Gradient::radial(1.0, 1.0, 2.0);
+        gradient.add_stop(0.0, Color::black());
+        gradient.add_stop(1.0, Color::white());
+        assert_eq!(gradient.color_at(1.0, 1.0).r, 0);
+        assert_eq!(gradient.color_at(1.0, 2.0).r, 128);
+        assert_eq!(gradient.color_at(3.0, 1.0).r, 255);
+
+        // A quarter turn clockwise from the start, where y points down
+        gradient = Gradient::conic(0.0, 0.0, 0.0);
+        gradient.add_stop(0.0, Color::black());
+        gradient.add_stop(1.0, Color::white());
+        assert_eq!(gradient.color_at(1.0, 0.0).r, 0);
+        assert_eq!(gradient.color_at(0.0, 1.0).r, 64);
+        assert_eq!(gradient.color_at(-1.0, 0.0).r, 128);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{Color, FrameBuffer, GraphicBuffer};
+
+/// Decides how the unbounded values in an HdrBuffer are squeezed down into
+/// the 0-255 range of a FrameBuffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMap {
+    /// Values are used as they are, with anything brighter than 1 cut off
+    Linear,
+    /// Each value v becomes v / (1 + v), which keeps detail in bright areas
+    /// while leaving dark areas mostly alone
+    Reinhard,
+    /// Values are scaled logarithmically so that the brightest value in the
+    /// buffer becomes 1, which suits density plots where a few pixels are
+    /// many times brighter than the rest
+    Log,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, which gives a
+    /// slight S-shaped contrast curve and rolls off highlights smoothly
+    Aces,
+}
+
+impl ToneMap {
+    /// Maps a single value, after exposure, into the range 0 to 1. The log
+    /// mapping needs the brightest value in the buffer, after exposure.
+    fn apply(self, value: f32, brightest: f32) -> f32 {
+        let value = value.max(0.0);
+        let mapped = match self {
+            ToneMap::Linear => value,
+            ToneMap::Reinhard => value / (1.0 + value),
+            ToneMap::Log => {
+                if brightest <= 0.0 {
+                    0.0
+                } else {
+                    value.ln_1p() / brightest.ln_1p()
+                }
+            }
+            ToneMap::Aces => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// A graphical buffer that adds up light instead of covering what's there.
+/// Each channel is a float where 1 is the brightest a Color can be, but which
+/// can keep growing past that without losing anything. Drawing a Color adds
+/// it to the pixel, scaled by its alpha.
+pub struct HdrBuffer {
+    pixels: Vec<f32>,
+    width: u32,
+    height: u32,
+}
+
+impl HdrBuffer {
+    /// Creates a new HdrBuffer with every pixel at 0
+    pub fn new(width: u32, height: u32) -> HdrBuffer {
+        HdrBuffer {
+            pixels: vec![0.0; (width * height * 3) as usize],
+            width,
+            height,
+        }
+    }
+
+    /// Sets every pixel back to 0
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|value| *value = 0.0);
+    }
+
+    /// Adds the given amounts of light to a pixel. Unlike drawing a Color,
+    /// the amounts can be any size.
+    pub fn add_point(&mut self, x: i64, y: i64, r: f32, g: f32, b: f32) {
+        if let Some(offset) = self.offset(x, y) {
+            self.pixels[offset] += r;
+            self.pixels[offset + 1] += g;
+            self.pixels[offset + 2] += b;
+        }
+    }
+
+    /// Gets the amounts of light in a pixel
+    pub fn get_value(&self, x: i64, y: i64) -> Option<(f32, f32, f32)> {
+        self.offset(x, y).map(|offset| {
+            (
+                self.pixels[offset],
+                self.pixels[offset + 1],
+                self.pixels[offset + 2],
+            )
+        })
+    }
+
+    /// Multiplies every pixel by the factor. Scaling by a little less than 1
+    /// before each frame makes old drawing fade out, leaving trails.
+    pub fn scale(&mut self, factor: f32) {
+        self.pixels.iter_mut().for_each(|value| *value *= factor);
+    }
+
+    /// Returns the brightest value of any channel in the buffer
+    pub fn brightest(&self) -> f32 {
+        self.pixels.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Resolves the buffer into a FrameBuffer, multiplying every value by the
+    /// exposure before tone mapping it
+    pub fn to_frame_buffer(&self, tone_map: ToneMap, exposure: f32) -> FrameBuffer {
+        let brightest = self.brightest() * exposure;
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|&value| (tone_map.apply(value * exposure, brightest) * 255.0).round() as u8)
+            .collect();
+
+        FrameBuffer {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Gets the offset of the given pixel, if it's inside of the buffer
+    fn offset(&self, x: i64, y: i64) -> Option<usize> {
+        if x < 0 || x >= self.width as i64 || y < 0 || y >= self.height as i64 {
+            None
+        } else {
+            Some(((y * (self.width as i64) + x) * 3) as usize)
+        }
+    }
+}
+
+impl GraphicBuffer<Color> for HdrBuffer {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gets the pixel as a Color, cutting off anything brighter than 1
+    fn get_point(&self, x: i64, y: i64) -> Option<Color> {
+        self.get_value(x, y).map(|(r, g, b)| {
+            let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            Color::rgb(channel(r), channel(g), channel(b))
+        })
+    }
+
+    /// Adds the color to the pixel, scaled by its alpha
+    fn put_point(&mut self, x: i64, y: i64, color: Color) {
+        let scale = color.alpha as f32 / (255.0 * 255.0);
+        self.add_point(
+            x,
+            y,
+            color.r as f32 * scale,
+            color.g as f32 * scale,
+            color.b as f32 * scale,
+        );
+    }
+}
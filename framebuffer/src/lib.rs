@@ -1,4 +1,52 @@
+use clip::Clip;
 use std::io;
+use transform::CanvasState;
+
+mod antialias;
+mod blend;
+mod blit;
+mod clip;
+mod colormap;
+mod colorspace;
+mod curve;
+mod deflate;
+mod ellipse;
+mod gif;
+mod gradient;
+mod hdr;
+mod netpbm;
+mod path;
+mod png;
+mod polygon;
+mod quantize;
+mod resample;
+mod rgba;
+mod sink;
+mod text;
+mod transform;
+mod wide;
+mod y4m;
+
+pub use blend::BlendMode;
+pub use blit::Flip;
+pub use colormap::Colormap;
+pub use colorspace::{ColorSpace, Hsl, Hsv, LinearRgb, Oklab, Oklch};
+pub use gif::{GifPalette, GifWriter};
+pub use gradient::{Gradient, Spread};
+pub use hdr::{HdrBuffer, ToneMap};
+pub use netpbm::{read_netpbm, NetpbmImage, NetpbmReader};
+pub use path::Path;
+pub use polygon::FillRule;
+pub use quantize::{quantize, QuantizeMethod, Quantized};
+pub use resample::Filter;
+pub use rgba::RgbaFrameBuffer;
+pub use sink::{
+    frame_limit_from_args, open_sink, sink_from_args, FrameSink, ImageFormat, ImageSequence,
+    PngSink, PpmSink,
+};
+pub use text::{TextAlign, TextBaseline};
+pub use transform::Transform;
+pub use y4m::{Chroma, Y4mWriter};
 
 /// Writes all the contents of the buffer to the output stream, breaking down
 /// the buffer into chunks as necessary
@@ -69,6 +117,19 @@ impl Color {
         Color::rgba(r, g, b, self.alpha)
     }
 
+    /// Creates a new color from combining this color with the other color
+    /// using the given blend mode, and then alpha blending the result over
+    /// this color. The output color's alpha is the same as this alpha.
+    pub fn blend_with(&self, other: Color, mode: BlendMode) -> Color {
+        let blended = Color::rgba(
+            mode.apply(self.r, other.r),
+            mode.apply(self.g, other.g),
+            mode.apply(self.b, other.b),
+            other.alpha,
+        );
+        self.blend(blended)
+    }
+
     /// Returns a Color representing pure white
     pub fn white() -> Color {
         Color::rgb(255, 255, 255)
@@ -85,6 +146,12 @@ pub trait GraphicBuffer<T: Copy> {
     fn height(&self) -> u32;
     fn put_point(&mut self, x: i64, y: i64, color: T);
     fn get_point(&self, x: i64, y: i64) -> Option<T>;
+
+    /// Puts the point, combining it with what's already there using the given
+    /// blend mode. Buffers that can't blend their elements ignore the mode.
+    fn blend_point(&mut self, x: i64, y: i64, color: T, _mode: BlendMode) {
+        self.put_point(x, y, color);
+    }
 }
 
 /// A graphical buffer containing pixel colors
@@ -106,12 +173,98 @@ impl FrameBuffer {
         }
     }
 
+    /// Reads a NetPBM image of any format from the stream. Grayscale images
+    /// are expanded to RGB and alpha channels are ignored.
+    pub fn read(input: &mut impl io::BufRead) -> io::Result<FrameBuffer> {
+        read_netpbm(input).map(NetpbmImage::into_frame_buffer)
+    }
+
     /// Dumps the framebuffer as a binary PPM image
     pub fn write(&self, output: &mut impl io::Write) -> io::Result<()> {
         let header = format!("P6\n{} {}\n255\n", self.width, self.height);
         write_all(output, header.as_bytes())?;
         write_all(output, &self.pixels)
     }
+
+    /// Dumps the framebuffer as an RGB PNG image, with each (keyword, text)
+    /// pair stored as a tEXt chunk. The text has to be Latin-1, and keywords
+    /// that PNG doesn't allow are rejected.
+    pub fn write_png(&self, output: &mut impl io::Write, text: &[(&str, &str)]) -> io::Result<()> {
+        png::write_png(
+            output,
+            self.width,
+            self.height,
+            png::PngColor::Rgb,
+            &self.pixels,
+            text,
+        )
+    }
+
+    /// Dumps the framebuffer as an RGBA PNG image, taking the alpha channel
+    /// from the given stencil. The stencil must be the same size as the
+    /// framebuffer.
+    pub fn write_png_alpha(
+        &self,
+        output: &mut impl io::Write,
+        alpha: &StencilBuffer,
+        text: &[(&str, &str)],
+    ) -> io::Result<()> {
+        let pixels = self.interleave_alpha(alpha)?;
+        png::write_png(
+            output,
+            self.width,
+            self.height,
+            png::PngColor::Rgba,
+            &pixels,
+            text,
+        )
+    }
+
+    /// Dumps the framebuffer as a PAM image with the RGB tuple type
+    pub fn write_pam(&self, output: &mut impl io::Write) -> io::Result<()> {
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 3\nMAXVAL 255\nTUPLTYPE RGB\nENDHDR\n",
+            self.width, self.height
+        );
+        write_all(output, header.as_bytes())?;
+        write_all(output, &self.pixels)
+    }
+
+    /// Dumps the framebuffer as a PAM image with the RGB_ALPHA tuple type,
+    /// taking the alpha channel from the given stencil. The stencil must be
+    /// the same size as the framebuffer.
+    pub fn write_pam_alpha(
+        &self,
+        output: &mut impl io::Write,
+        alpha: &StencilBuffer,
+    ) -> io::Result<()> {
+        let pixels = self.interleave_alpha(alpha)?;
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height
+        );
+        write_all(output, header.as_bytes())?;
+        write_all(output, &pixels)
+    }
+
+    /// Combines the pixels of this buffer with the alpha values from the
+    /// stencil into packed RGBA samples
+    fn interleave_alpha(&self, alpha: &StencilBuffer) -> io::Result<Vec<u8>> {
+        if alpha.width != self.width || alpha.height != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "alpha stencil does not match the framebuffer size",
+            ));
+        }
+
+        let mut pixels = Vec::with_capacity(alpha.pixels.len() * 4);
+        for (color, &alpha) in self.pixels.chunks(3).zip(alpha.pixels.iter()) {
+            pixels.extend_from_slice(color);
+            pixels.push(alpha);
+        }
+
+        Ok(pixels)
+    }
 }
 
 impl GraphicBuffer<Color> for FrameBuffer {
@@ -126,7 +279,7 @@ impl GraphicBuffer<Color> for FrameBuffer {
     fn get_point(&self, x: i64, y: i64) -> Option<Color> {
         if x < 0 || x >= self.width as i64 {
             None
-        } else if y < 0 || x >= self.height as i64 {
+        } else if y < 0 || y >= self.height as i64 {
             None
         } else {
             let offset = ((y * (self.width as i64) * 3) + (x * 3)) as usize;
@@ -172,6 +325,14 @@ impl GraphicBuffer<Color> for FrameBuffer {
             self.pixels[offset + 2] = b;
         }
     }
+
+    fn blend_point(&mut self, x: i64, y: i64, color: Color, mode: BlendMode) {
+        if mode == BlendMode::Normal {
+            self.put_point(x, y, color);
+        } else if let Some(base) = self.get_point(x, y) {
+            self.put_point(x, y, base.blend_with(color, mode));
+        }
+    }
 }
 
 /// A masking buffer containing simple integers
@@ -192,6 +353,22 @@ impl StencilBuffer {
             height,
         }
     }
+
+    /// Reads a NetPBM image of any format from the stream. Color images are
+    /// reduced to their luma and alpha channels are ignored.
+    pub fn read(input: &mut impl io::BufRead) -> io::Result<StencilBuffer> {
+        read_netpbm(input).map(NetpbmImage::into_stencil_buffer)
+    }
+
+    /// Dumps the stencil as a PAM image with the GRAYSCALE tuple type
+    pub fn write_pam(&self, output: &mut impl io::Write) -> io::Result<()> {
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 1\nMAXVAL 255\nTUPLTYPE GRAYSCALE\nENDHDR\n",
+            self.width, self.height
+        );
+        write_all(output, header.as_bytes())?;
+        write_all(output, &self.pixels)
+    }
 }
 
 impl GraphicBuffer<u8> for StencilBuffer {
@@ -206,7 +383,7 @@ impl GraphicBuffer<u8> for StencilBuffer {
     fn get_point(&self, x: i64, y: i64) -> Option<u8> {
         if x < 0 || x >= self.width as i64 {
             None
-        } else if y < 0 || x >= self.height as i64 {
+        } else if y < 0 || y >= self.height as i64 {
             None
         } else {
             let offset = ((y * (self.width as i64)) + x) as usize;
@@ -217,7 +394,7 @@ impl GraphicBuffer<u8> for StencilBuffer {
     fn put_point(&mut self, x: i64, y: i64, color: u8) {
         if x < 0 || x >= self.width as i64 {
             return;
-        } else if y < 0 || x >= self.height as i64 {
+        } else if y < 0 || y >= self.height as i64 {
             return;
         } else {
             let offset = ((y * (self.width as i64)) + x) as usize;
@@ -231,6 +408,15 @@ pub struct Canvas<Element: Copy, Buffer: GraphicBuffer<Element>> {
     buffer: Buffer,
     fill: Element,
     stroke: Element,
+    line_width: f64,
+    fill_rule: FillRule,
+    blend_mode: BlendMode,
+    text_scale: i64,
+    text_align: TextAlign,
+    text_baseline: TextBaseline,
+    transform: Transform,
+    clip: Clip,
+    saved: Vec<CanvasState<Element>>,
 }
 
 impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
@@ -241,6 +427,15 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
             buffer,
             fill,
             stroke,
+            line_width: 1.0,
+            fill_rule: FillRule::NonZero,
+            blend_mode: BlendMode::Normal,
+            text_scale: 1,
+            text_align: TextAlign::Left,
+            text_baseline: TextBaseline::Top,
+            transform: Transform::identity(),
+            clip: Clip::new(),
+            saved: Vec::new(),
         }
     }
 
@@ -264,9 +459,12 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
         self.buffer.get_point(x, y)
     }
 
-    /// Puts the given point from the underlying canvas
+    /// Puts the given point onto the underlying canvas using the current
+    /// blend mode, unless it's outside of the clip region
     pub fn put_point(&mut self, x: i64, y: i64, color: Element) {
-        self.buffer.put_point(x, y, color)
+        if self.clip.contains(x, y) {
+            self.buffer.blend_point(x, y, color, self.blend_mode)
+        }
     }
 
     /// Applies a mask function from the other buffer onto this canvas's buffer
@@ -315,27 +513,63 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
         self.stroke = stroke;
     }
 
+    /// Sets the width of the lines drawn by the stroke methods, in pixels
+    pub fn set_line_width(&mut self, width: f64) {
+        self.line_width = width.max(0.0);
+    }
+
+    /// Gets the width of the lines drawn by the stroke methods
+    pub fn line_width(&self) -> f64 {
+        self.line_width
+    }
+
+    /// Sets the rule used to decide which parts of a shape are filled when
+    /// its outline crosses itself
+    pub fn set_fill_rule(&mut self, rule: FillRule) {
+        self.fill_rule = rule;
+    }
+
+    /// Sets how the colors drawn by later fills and strokes are combined with
+    /// what's already in the buffer
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    /// Gets the current blend mode
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
     /// Draws a single pixel at the given point using the current fill
     pub fn fill_point(&mut self, x: i64, y: i64) {
-        self.buffer.put_point(x, y, self.fill);
+        let (px, py) = self.transform_point(x, y);
+        self.put_point(px, py, self.fill);
     }
 
     /// Draws a single pixel at the given point using the current stroke
     pub fn stroke_point(&mut self, x: i64, y: i64) {
-        self.buffer.put_point(x, y, self.stroke);
+        let (px, py) = self.transform_point(x, y);
+        self.put_point(px, py, self.stroke);
     }
 
-    /// Fills the entire buffer using the currently assigned fill value
+    /// Fills the entire buffer using the currently assigned fill value. This
+    /// ignores the current transform, but not the clip region.
     pub fn fill(&mut self) {
         for y in 0..self.buffer.height() {
             for x in 0..self.buffer.width() {
-                self.fill_point(x as i64, y as i64);
+                self.put_point(x as i64, y as i64, self.fill);
             }
         }
     }
 
     /// Fills the given region of the framebuffer with the current fill color
     pub fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64) {
+        if self.is_transformed() {
+            let fill = self.fill;
+            self.transformed_fill_rect(x, y, width, height, |_, _| fill);
+            return;
+        }
+
         for py in y..(y + height) {
             for px in x..(x + width) {
                 self.fill_point(px, py);
@@ -348,11 +582,16 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     where
         F: Fn(f64, f64) -> Element,
     {
+        if self.is_transformed() {
+            self.transformed_fill_rect(x, y, width, height, gradient);
+            return;
+        }
+
         for py in y..(y + height) {
             let yratio = (py - y) as f64 / height as f64;
             for px in x..(x + width) {
                 let xratio = (px - x) as f64 / width as f64;
-                self.buffer.put_point(px, py, gradient(xratio, yratio));
+                self.put_point(px, py, gradient(xratio, yratio));
             }
         }
     }
@@ -360,16 +599,8 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     /// Draws a border around the given region of the framebuffer with the
     /// current stroke color
     pub fn stroke_rect(&mut self, x: i64, y: i64, width: i64, height: i64) {
-        for py in y..(y + height) {
-            if py == y || y == (y + height) - 1 {
-                for px in x..(x + width) {
-                    self.stroke_point(py, px);
-                }
-            } else {
-                self.stroke_point(py, x);
-                self.stroke_point(py, x + width - 1);
-            }
-        }
+        let stroke = self.stroke;
+        self.gstroke_rect(x, y, width, height, |_, _| stroke);
     }
 
     /// Draws a border around the given region of the framebuffer with the
@@ -378,24 +609,26 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     where
         F: Fn(f64, f64) -> Element,
     {
-        for py in y..(y + height) {
-            let yratio = (py - y) as f64 / height as f64;
-            if py == y || y == (y + height) - 1 {
-                for px in x..(x + width) {
-                    let xratio = (px - x) as f64 / width as f64;
-                    self.buffer.put_point(py, px, gradient(xratio, yratio));
-                }
-            } else {
-                self.buffer.put_point(py, x, gradient(0.0, yratio));
-                self.buffer
-                    .put_point(py, x + width - 1, gradient(1.0, yratio));
-            }
+        if self.is_transformed() {
+            self.transformed_stroke_rect(x, y, width, height, gradient);
+        } else {
+            self.wide_rect(x, y, width, height, gradient);
         }
     }
 
     /// Draws a straight line between the two points using the current stroke
     /// color
     pub fn stroke_line(&mut self, x: i64, y: i64, x2: i64, y2: i64) {
+        if self.is_transformed() {
+            let stroke = self.stroke;
+            self.transformed_line(x, y, x2, y2, self.line_width, |_| stroke);
+            return;
+        } else if self.line_width != 1.0 {
+            let stroke = self.stroke;
+            self.wide_line(x as f64, y as f64, x2 as f64, y2 as f64, |_| stroke);
+            return;
+        }
+
         if x == x2 {
             for py in y..y2 {
                 self.stroke_point(x, py);
@@ -483,6 +716,12 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     /// Draws a straight line between the two points using the current fill
     /// color
     pub fn fill_line(&mut self, x: i64, y: i64, x2: i64, y2: i64) {
+        if self.is_transformed() {
+            let fill = self.fill;
+            self.transformed_line(x, y, x2, y2, 1.0, |_| fill);
+            return;
+        }
+
         if x == x2 {
             for py in y..y2 {
                 self.fill_point(x, py);
@@ -534,18 +773,26 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     where
         F: Fn(f64) -> Element,
     {
+        if self.is_transformed() {
+            self.transformed_line(x, y, x2, y2, self.line_width, gradient);
+            return;
+        } else if self.line_width != 1.0 {
+            self.wide_line(x as f64, y as f64, x2 as f64, y2 as f64, gradient);
+            return;
+        }
+
         if x == x2 {
             let length = (y2 - y) as f64;
             for py in y..y2 {
                 let yratio = (py - y) as f64 / length;
-                self.buffer.put_point(x, py, gradient(yratio));
+                self.put_point(x, py, gradient(yratio));
             }
             return;
         } else if y == y2 {
             let length = (x2 - x) as f64;
             for px in x..x2 {
                 let xratio = (px - x) as f64 / length;
-                self.buffer.put_point(px, y, gradient(xratio));
+                self.put_point(px, y, gradient(xratio));
             }
             return;
         }
@@ -564,8 +811,7 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
         loop {
             let point_length = (((px - x) as f64).powf(2.0) + ((py - y) as f64).powf(2.0)).sqrt();
 
-            self.buffer
-                .put_point(px, py, gradient(point_length / length));
+            self.put_point(px, py, gradient(point_length / length));
 
             let next_error = 2 * error;
             if next_error >= deltay {
@@ -591,6 +837,16 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     /// Draws a circle's perimeter around the given point using the current
     /// stroke color
     pub fn stroke_circle(&mut self, x: i64, y: i64, r: i64) {
+        if self.is_transformed() {
+            let stroke = self.stroke;
+            self.transformed_stroke_circle(x, y, r, |_| stroke);
+            return;
+        } else if self.line_width != 1.0 {
+            let stroke = self.stroke;
+            self.wide_circle(x, y, r, |_| stroke);
+            return;
+        }
+
         /*
         Derivation, assuming that x and y are the origin (the offset can be done
         later):
@@ -647,27 +903,31 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     where
         F: Fn(f64) -> Element,
     {
+        if self.is_transformed() {
+            self.transformed_stroke_circle(x, y, r, gradient);
+            return;
+        } else if self.line_width != 1.0 {
+            self.wide_circle(x, y, r, gradient);
+            return;
+        }
+
         let mut error = -2 * r + 2;
 
         let mut relx = -r;
         let mut rely = 0;
 
         while relx <= 0 {
-            let q1_angle = ((y + rely) as f64).atan2((x + relx) as f64);
-            self.buffer
-                .put_point(x + relx, y + rely, gradient(q1_angle));
+            let q1_angle = (rely as f64).atan2(relx as f64);
+            self.put_point(x + relx, y + rely, gradient(q1_angle));
 
-            let q2_angle = ((y + rely) as f64).atan2((x - relx) as f64);
-            self.buffer
-                .put_point(x - relx, y + rely, gradient(q2_angle));
+            let q2_angle = (rely as f64).atan2(-relx as f64);
+            self.put_point(x - relx, y + rely, gradient(q2_angle));
 
-            let q3_angle = ((y - rely) as f64).atan2((x + relx) as f64);
-            self.buffer
-                .put_point(x + relx, y - rely, gradient(q3_angle));
+            let q3_angle = (-rely as f64).atan2(relx as f64);
+            self.put_point(x + relx, y - rely, gradient(q3_angle));
 
-            let q4_angle = ((y - rely) as f64).atan2((x - relx) as f64);
-            self.buffer
-                .put_point(x - relx, y - rely, gradient(q4_angle));
+            let q4_angle = (-rely as f64).atan2(-relx as f64);
+            self.put_point(x - relx, y - rely, gradient(q4_angle));
 
             let next_error = 2 * error;
             if next_error >= 2 * relx + 1 {
@@ -684,6 +944,12 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
 
     /// Fills a circle around the given point
     pub fn fill_circle(&mut self, x: i64, y: i64, r: i64) {
+        if self.is_transformed() {
+            let fill = self.fill;
+            self.transformed_fill_circle(x, y, r, |_, _| fill);
+            return;
+        }
+
         let mut error = -2 * r + 2;
 
         let mut relx = -r;
@@ -711,6 +977,11 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
     where
         F: Fn(f64, f64) -> Element,
     {
+        if self.is_transformed() {
+            self.transformed_fill_circle(x, y, r, gradient);
+            return;
+        }
+
         let mut error = -2 * r + 2;
 
         let mut relx = -r;
@@ -733,8 +1004,8 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
 
                     let angle = ((py - y) as f64).atan2((px - x) as f64);
                     let angle2 = ((py2 - y) as f64).atan2((px - x) as f64);
-                    self.buffer.put_point(px, py, gradient(angle, distance));
-                    self.buffer.put_point(px, py2, gradient(angle2, distance));
+                    self.put_point(px, py, gradient(angle, distance));
+                    self.put_point(px, py2, gradient(angle2, distance));
                 }
 
                 rely += 1;
@@ -743,3 +1014,115 @@ impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits a PAM image into its header lines and its samples
+    fn split_pam(output: &[u8]) -> (Vec<&str>, &[u8]) {
+        let marker = b"ENDHDR\n";
+        let end = output
+            .windows(marker.len())
+            .position(|window| window == marker)
+            .unwrap()
+            + marker.len();
+        let header = std::str::from_utf8(&output[..end]).unwrap();
+        (header.lines().collect(), &output[end..])
+    }
+
+    /// Builds a 3x2 framebuffer and a stencil of the same size
+    fn sample_buffers() -> (FrameBuffer, StencilBuffer) {
+        let mut frame = FrameBuffer::new(3, 2);
+        let mut stencil = StencilBuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let value = (y * 3 + x) as u8;
+                frame.put_point(x, y, Color::rgb(value, value * 10, 255 - value));
+                stencil.put_point(x, y, value * 50);
+            }
+        }
+        (frame, stencil)
+    }
+
+    #[test]
+    fn pam_rgb() {
+        let (frame, _) = sample_buffers();
+        let mut output = Vec::new();
+        frame.write_pam(&mut output).unwrap();
+
+        let (header, samples) = split_pam(&output);
+        assert_eq!(
+            header,
+            [
+                "P7",
+                "WIDTH 3",
+                "HEIGHT 2",
+                "DEPTH 3",
+                "MAXVAL 255",
+                "TUPLTYPE RGB",
+                "ENDHDR"
+            ]
+        );
+        assert_eq!(samples[..6], [0, 0, 255, 1, 10, 254]);
+        assert_eq!(samples.len(), 3 * 2 * 3);
+        assert!(FrameBuffer::read(&mut &output[..]).unwrap().pixels == frame.pixels);
+    }
+
+    #[test]
+    fn pam_rgb_alpha() {
+        let (frame, stencil) = sample_buffers();
+        let mut output = Vec::new();
+        frame.write_pam_alpha(&mut output, &stencil).unwrap();
+
+        let (header, samples) = split_pam(&output);
+        assert_eq!(
+            header,
+            [
+                "P7",
+                "WIDTH 3",
+                "HEIGHT 2",
+                "DEPTH 4",
+                "MAXVAL 255",
+                "TUPLTYPE RGB_ALPHA",
+                "ENDHDR"
+            ]
+        );
+        assert_eq!(samples[..8], [0, 0, 255, 0, 1, 10, 254, 50]);
+        assert_eq!(samples[20..], [5, 50, 250, 250]);
+
+        // The alpha has to line up with the pixels
+        let mut output = Vec::new();
+        let error = frame
+            .write_pam_alpha(&mut output, &StencilBuffer::new(2, 3))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn pam_grayscale() {
+        let (_, stencil) = sample_buffers();
+        let mut output = Vec::new();
+        stencil.write_pam(&mut output).unwrap();
+
+        let (header, samples) = split_pam(&output);
+        assert_eq!(
+            header,
+            [
+                "P7",
+                "WIDTH 3",
+                "HEIGHT 2",
+                "DEPTH 1",
+                "MAXVAL 255",
+                "TUPLTYPE GRAYSCALE",
+                "ENDHDR"
+            ]
+        );
+        assert_eq!(samples, [0, 50, 100, 150, 200, 250]);
+        assert_eq!(
+            StencilBuffer::read(&mut &output[..]).unwrap().pixels,
+            stencil.pixels
+        );
+    }
+}
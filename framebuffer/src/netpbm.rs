@@ -0,0 +1,707 @@
+use crate::{FrameBuffer, RgbaFrameBuffer, StencilBuffer};
+use std::io;
+
+/// An image decoded from a NetPBM stream. PBM and PGM images, as well as PAM
+/// images with one or two channels, are decoded into StencilBuffers. PPM
+/// images and PAM images with three or four channels are decoded into
+/// FrameBuffers. If the PAM image has an alpha channel it is returned in its
+/// own StencilBuffer.
+pub enum NetpbmImage {
+    Color(FrameBuffer, Option<StencilBuffer>),
+    Gray(StencilBuffer, Option<StencilBuffer>),
+}
+
+impl NetpbmImage {
+    /// Converts the image into a FrameBuffer, copying grayscale values into
+    /// all three channels and dropping any alpha channel
+    pub fn into_frame_buffer(self) -> FrameBuffer {
+        match self {
+            NetpbmImage::Color(buffer, _) => buffer,
+            NetpbmImage::Gray(buffer, _) => {
+                let mut pixels = Vec::with_capacity(buffer.pixels.len() * 3);
+                for &value in buffer.pixels.iter() {
+                    pixels.extend_from_slice(&[value, value, value]);
+                }
+
+                FrameBuffer {
+                    pixels,
+                    width: buffer.width,
+                    height: buffer.height,
+                }
+            }
+        }
+    }
+
+    /// Converts the image into an RgbaFrameBuffer, copying grayscale values
+    /// into all three channels. Images without an alpha channel are fully
+    /// opaque.
+    pub fn into_rgba_frame_buffer(self) -> RgbaFrameBuffer {
+        let (image, alpha) = match self {
+            NetpbmImage::Color(buffer, alpha) => (NetpbmImage::Color(buffer, None), alpha),
+            NetpbmImage::Gray(buffer, alpha) => (NetpbmImage::Gray(buffer, None), alpha),
+        };
+
+        RgbaFrameBuffer::from_parts(&image.into_frame_buffer(), alpha.as_ref())
+    }
+
+    /// Converts the image into a StencilBuffer, reducing colors to their luma
+    /// and dropping any alpha channel
+    pub fn into_stencil_buffer(self) -> StencilBuffer {
+        match self {
+            NetpbmImage::Gray(buffer, _) => buffer,
+            NetpbmImage::Color(buffer, _) => {
+                let pixels = buffer
+                    .pixels
+                    .chunks(3)
+                    .map(|pixel| {
+                        let luma =
+                            77 * pixel[0] as u32 + 150 * pixel[1] as u32 + 29 * pixel[2] as u32;
+                        ((luma + 128) >> 8) as u8
+                    })
+                    .collect();
+
+                StencilBuffer {
+                    pixels,
+                    width: buffer.width,
+                    height: buffer.height,
+                }
+            }
+        }
+    }
+}
+
+/// The most samples that are set aside before any of them have been read.
+/// Headers can claim any size, so larger images grow their buffers as the
+/// data actually arrives.
+const MAX_PREALLOCATE: usize = 1 << 20;
+
+/// How many bytes of binary image data are read at a time
+const CHUNK_SIZE: usize = 1 << 16;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Scales a sample with the given maxval into the 0-255 range
+fn scale_sample(value: u32, maxval: u32) -> u8 {
+    if maxval == 255 {
+        value as u8
+    } else {
+        ((value.min(maxval) * 255 + maxval / 2) / maxval) as u8
+    }
+}
+
+/// The dimensions and sample layout of an image, as read from its header
+struct Header {
+    width: u32,
+    height: u32,
+    depth: usize,
+    maxval: u32,
+}
+
+impl Header {
+    fn check(&self) -> io::Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(invalid_data("NetPBM image has no pixels"));
+        }
+
+        if self.maxval == 0 || self.maxval > 65535 {
+            return Err(invalid_data("NetPBM maxval must be between 1 and 65535"));
+        }
+
+        if self.depth == 0 || self.depth > 4 {
+            return Err(invalid_data("NetPBM depth must be between 1 and 4"));
+        }
+
+        // The buffers keep their byte counts in a u32
+        let bytes = (self.width as u64)
+            .checked_mul(self.height as u64)
+            .and_then(|pixels| pixels.checked_mul(3));
+        if bytes.is_none_or(|bytes| bytes > u32::MAX as u64) {
+            return Err(invalid_data("NetPBM image is too large"));
+        }
+
+        Ok(())
+    }
+
+    fn pixel_count(&self) -> io::Result<usize> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .ok_or_else(|| invalid_data("NetPBM image is too large"))
+    }
+
+    fn sample_count(&self) -> io::Result<usize> {
+        self.pixel_count()?
+            .checked_mul(self.depth)
+            .ok_or_else(|| invalid_data("NetPBM image is too large"))
+    }
+}
+
+/// Reads tokens and samples from the underlying stream one byte at a time,
+/// without consuming anything past the end of the current image
+struct Parser<'a, R: io::BufRead> {
+    input: &'a mut R,
+}
+
+impl<'a, R: io::BufRead> Parser<'a, R> {
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        let buffer = self.input.fill_buf()?;
+        Ok(buffer.first().copied())
+    }
+
+    fn next(&mut self) -> io::Result<u8> {
+        match self.peek()? {
+            Some(byte) => {
+                self.input.consume(1);
+                Ok(byte)
+            }
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        }
+    }
+
+    /// Skips over whitespace and comments, returning false if the end of the
+    /// stream was reached
+    fn skip_whitespace(&mut self) -> io::Result<bool> {
+        loop {
+            match self.peek()? {
+                None => return Ok(false),
+                Some(b'#') => {
+                    while let Some(byte) = self.peek()? {
+                        self.input.consume(1);
+                        if byte == b'\n' || byte == b'\r' {
+                            break;
+                        }
+                    }
+                }
+                Some(byte) if byte.is_ascii_whitespace() => self.input.consume(1),
+                Some(_) => return Ok(true),
+            }
+        }
+    }
+
+    /// Reads the given number of bytes in chunks, passing each chunk to the
+    /// callback as it arrives
+    fn chunks<F>(&mut self, length: usize, mut callback: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut chunk = vec![0; length.min(CHUNK_SIZE)];
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk = &mut chunk[..remaining.min(CHUNK_SIZE)];
+            self.input.read_exact(chunk)?;
+            callback(chunk);
+            remaining -= chunk.len();
+        }
+        Ok(())
+    }
+
+    /// Reads a run of non-whitespace characters
+    fn token(&mut self) -> io::Result<String> {
+        if !self.skip_whitespace()? {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut token = String::new();
+        while let Some(byte) = self.peek()? {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            token.push(byte as char);
+            self.input.consume(1);
+        }
+
+        Ok(token)
+    }
+
+    fn number(&mut self) -> io::Result<u32> {
+        self.token()?
+            .parse()
+            .map_err(|_| invalid_data("expected a number in NetPBM data"))
+    }
+
+    /// Reads the rest of a line, used for the PAM header
+    fn line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        loop {
+            let byte = self.next()?;
+            if byte == b'\n' {
+                return Ok(line);
+            }
+            line.push(byte as char);
+        }
+    }
+
+    /// Reads the header for any of the P1-P6 formats, not including the magic
+    /// number. Binary formats have exactly one whitespace byte between the
+    /// header and the image data.
+    fn classic_header(&mut self, depth: usize, has_maxval: bool) -> io::Result<Header> {
+        let width = self.number()?;
+        let height = self.number()?;
+        let maxval = if has_maxval { self.number()? } else { 1 };
+        self.next()?;
+
+        Ok(Header {
+            width,
+            height,
+            depth,
+            maxval,
+        })
+    }
+
+    fn pam_header(&mut self) -> io::Result<Header> {
+        let mut width = None;
+        let mut height = None;
+        let mut depth = None;
+        let mut maxval = None;
+
+        loop {
+            let line = self.line()?;
+            let mut fields = line.split_whitespace();
+            let key = match fields.next() {
+                None => continue,
+                Some(key) if key.starts_with('#') => continue,
+                Some(key) => key,
+            };
+
+            let mut value = || -> io::Result<u32> {
+                fields
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| invalid_data("expected a number in PAM header"))
+            };
+
+            match key {
+                "WIDTH" => width = Some(value()?),
+                "HEIGHT" => height = Some(value()?),
+                "DEPTH" => depth = Some(value()? as usize),
+                "MAXVAL" => maxval = Some(value()?),
+                "TUPLTYPE" => (),
+                "ENDHDR" => break,
+                _ => return Err(invalid_data("unknown field in PAM header")),
+            }
+        }
+
+        match (width, height, depth, maxval) {
+            (Some(width), Some(height), Some(depth), Some(maxval)) => Ok(Header {
+                width,
+                height,
+                depth,
+                maxval,
+            }),
+            _ => Err(invalid_data("PAM header is missing a required field")),
+        }
+    }
+
+    /// Reads all the samples for a plain (ASCII) PGM or PPM image
+    fn ascii_samples(&mut self, header: &Header) -> io::Result<Vec<u8>> {
+        let count = header.sample_count()?;
+        let mut samples = Vec::with_capacity(count.min(MAX_PREALLOCATE));
+        for _ in 0..count {
+            samples.push(scale_sample(self.number()?, header.maxval));
+        }
+        Ok(samples)
+    }
+
+    /// Reads all the samples for a binary PGM, PPM or PAM image, which are
+    /// two bytes wide when the maxval doesn't fit in a byte
+    fn binary_samples(&mut self, header: &Header) -> io::Result<Vec<u8>> {
+        let count = header.sample_count()?;
+        let mut samples = Vec::with_capacity(count.min(MAX_PREALLOCATE));
+
+        if header.maxval > 255 {
+            let length = count
+                .checked_mul(2)
+                .ok_or_else(|| invalid_data("NetPBM image is too large"))?;
+
+            // The chunks are all an even length, so samples never straddle them
+            self.chunks(length, |chunk| {
+                samples.extend(chunk.chunks(2).map(|pair| {
+                    scale_sample(u16::from_be_bytes([pair[0], pair[1]]) as u32, header.maxval)
+                }));
+            })?;
+        } else {
+            self.chunks(count, |chunk| {
+                samples.extend(
+                    chunk
+                        .iter()
+                        .map(|&sample| scale_sample(sample as u32, header.maxval)),
+                );
+            })?;
+        }
+
+        Ok(samples)
+    }
+
+    /// Reads the bits for a plain PBM image, where 1 is black. Unlike the other
+    /// plain formats the digits don't have to be separated by whitespace.
+    fn ascii_bits(&mut self, header: &Header) -> io::Result<Vec<u8>> {
+        let count = header.pixel_count()?;
+        let mut samples = Vec::with_capacity(count.min(MAX_PREALLOCATE));
+        while samples.len() < count {
+            if !self.skip_whitespace()? {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+
+            match self.next()? {
+                b'0' => samples.push(255),
+                b'1' => samples.push(0),
+                _ => return Err(invalid_data("expected a 0 or 1 in PBM data")),
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Reads the bits for a binary PBM image, where each row is padded out to
+    /// a whole number of bytes
+    fn binary_bits(&mut self, header: &Header) -> io::Result<Vec<u8>> {
+        let width = header.width as usize;
+        let stride = width.div_ceil(8);
+        let count = header.pixel_count()?;
+        let mut samples = Vec::with_capacity(count.min(MAX_PREALLOCATE));
+
+        for _ in 0..header.height {
+            let mut x = 0;
+            self.chunks(stride, |chunk| {
+                for &byte in chunk {
+                    // The padding bits at the end of the row are skipped
+                    for bit in (0..8).rev().take(width - x) {
+                        samples.push(if (byte >> bit) & 1 == 1 { 0 } else { 255 });
+                    }
+                    x = (x + 8).min(width);
+                }
+            })?;
+        }
+        Ok(samples)
+    }
+}
+
+/// Splits interleaved samples into their color channels and their alpha
+/// channel, if the image has one
+fn split_alpha(
+    header: &Header,
+    samples: Vec<u8>,
+    channels: usize,
+) -> (Vec<u8>, Option<StencilBuffer>) {
+    if header.depth == channels {
+        return (samples, None);
+    }
+
+    let pixel_count = samples.len() / header.depth;
+    let mut pixels = Vec::with_capacity(pixel_count * channels);
+    let mut alpha = Vec::with_capacity(pixel_count);
+    for tuple in samples.chunks(header.depth) {
+        pixels.extend_from_slice(&tuple[..channels]);
+        alpha.push(tuple[channels]);
+    }
+
+    let alpha = StencilBuffer {
+        pixels: alpha,
+        width: header.width,
+        height: header.height,
+    };
+    (pixels, Some(alpha))
+}
+
+fn build_image(header: &Header, samples: Vec<u8>) -> NetpbmImage {
+    if header.depth <= 2 {
+        let (pixels, alpha) = split_alpha(header, samples, 1);
+        let buffer = StencilBuffer {
+            pixels,
+            width: header.width,
+            height: header.height,
+        };
+        NetpbmImage::Gray(buffer, alpha)
+    } else {
+        let (pixels, alpha) = split_alpha(header, samples, 3);
+        let buffer = FrameBuffer {
+            pixels,
+            width: header.width,
+            height: header.height,
+        };
+        NetpbmImage::Color(buffer, alpha)
+    }
+}
+
+/// Reads the next image from the stream, returning None if the stream ends
+/// before another image starts
+fn read_next<R: io::BufRead>(input: &mut R) -> io::Result<Option<NetpbmImage>> {
+    let mut parser = Parser { input };
+    if !parser.skip_whitespace()? {
+        return Ok(None);
+    }
+
+    if parser.next()? != b'P' {
+        return Err(invalid_data("not a NetPBM image"));
+    }
+
+    let format = parser.next()?;
+    let header = match format {
+        b'1' | b'4' => parser.classic_header(1, false)?,
+        b'2' | b'5' => parser.classic_header(1, true)?,
+        b'3' | b'6' => parser.classic_header(3, true)?,
+        b'7' => {
+            parser.line()?;
+            parser.pam_header()?
+        }
+        _ => return Err(invalid_data("unknown NetPBM format")),
+    };
+    header.check()?;
+
+    let samples = match format {
+        b'1' => parser.ascii_bits(&header)?,
+        b'4' => parser.binary_bits(&header)?,
+        b'2' | b'3' => parser.ascii_samples(&header)?,
+        _ => parser.binary_samples(&header)?,
+    };
+
+    Ok(Some(build_image(&header, samples)))
+}
+
+/// Reads a single PBM, PGM, PPM or PAM image from the stream
+pub fn read_netpbm<R: io::BufRead>(input: &mut R) -> io::Result<NetpbmImage> {
+    read_next(input)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+}
+
+/// Reads successive images from a NetPBM stream, such as the output of one of
+/// the demos piped into stdin
+pub struct NetpbmReader<R: io::BufRead> {
+    input: R,
+    failed: bool,
+}
+
+impl<R: io::BufRead> NetpbmReader<R> {
+    /// Creates a new reader over the given stream
+    pub fn new(input: R) -> NetpbmReader<R> {
+        NetpbmReader {
+            input,
+            failed: false,
+        }
+    }
+}
+
+impl<R: io::BufRead> Iterator for NetpbmReader<R> {
+    type Item = io::Result<NetpbmImage>;
+
+    /// Reads the next image, stopping after the end of the stream or the
+    /// first error
+    fn next(&mut self) -> Option<io::Result<NetpbmImage>> {
+        if self.failed {
+            return None;
+        }
+
+        match read_next(&mut self.input) {
+            Ok(image) => image.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, GraphicBuffer};
+
+    fn read(data: &[u8]) -> io::Result<NetpbmImage> {
+        read_netpbm(&mut &data[..])
+    }
+
+    fn gray(data: &[u8]) -> (Vec<u8>, Option<Vec<u8>>) {
+        match read(data).unwrap() {
+            NetpbmImage::Gray(buffer, alpha) => (buffer.pixels, alpha.map(|alpha| alpha.pixels)),
+            NetpbmImage::Color(..) => panic!("expected a grayscale image"),
+        }
+    }
+
+    fn color(data: &[u8]) -> (Vec<u8>, Option<Vec<u8>>) {
+        match read(data).unwrap() {
+            NetpbmImage::Color(buffer, alpha) => (buffer.pixels, alpha.map(|alpha| alpha.pixels)),
+            NetpbmImage::Gray(..) => panic!("expected a color image"),
+        }
+    }
+
+    #[test]
+    fn plain_pbm() {
+        // Digits don't need whitespace between them, and 1 is black
+        let (pixels, alpha) = gray(b"P1\n# a comment\n3 2\n101\n0 1 0\n");
+        assert_eq!(pixels, [0, 255, 0, 255, 0, 255]);
+        assert!(alpha.is_none());
+    }
+
+    #[test]
+    fn binary_pbm_odd_width() {
+        // Each 10 pixel row takes two bytes, and the padding bits are ignored
+        let mut data = b"P4 10 2\n".to_vec();
+        data.extend_from_slice(&[0b1010_0000, 0b01_111111, 0b1111_1111, 0b10_000000]);
+
+        let (pixels, _) = gray(&data);
+        let row = |bits: &str| -> Vec<u8> {
+            bits.chars()
+                .map(|bit| if bit == '1' { 0 } else { 255 })
+                .collect()
+        };
+        assert_eq!(pixels[..10], row("1010000001")[..]);
+        assert_eq!(pixels[10..], row("1111111110")[..]);
+    }
+
+    #[test]
+    fn plain_pgm_and_ppm_scaling() {
+        let (pixels, _) = gray(b"P2\n3 1\n# maxval follows\n15\n0 7 15\n");
+        assert_eq!(pixels, [0, 119, 255]);
+
+        // Samples over the maxval are clamped to it
+        let (pixels, _) = color(b"P3 2 1 100  100 50 0  0 25 200\n");
+        assert_eq!(pixels, [255, 128, 0, 0, 64, 255]);
+    }
+
+    #[test]
+    fn binary_pgm_and_ppm() {
+        let (pixels, _) = gray(b"P5 2 2 255\n\x00\x40\x80\xff");
+        assert_eq!(pixels, [0, 64, 128, 255]);
+
+        let (pixels, _) = color(b"P6\n#comment\n1 1\n3\n\x01\x02\x03");
+        assert_eq!(pixels, [85, 170, 255]);
+    }
+
+    #[test]
+    fn sixteen_bit_samples() {
+        // Samples are big endian when the maxval needs two bytes
+        let (pixels, _) = gray(b"P5 3 1 65535\n\x00\x00\x80\x00\xff\xff");
+        assert_eq!(pixels, [0, 128, 255]);
+
+        let (pixels, _) = color(b"P6 1 1 1000\n\x03\xe8\x01\xf4\x00\x00");
+        assert_eq!(pixels, [255, 128, 0]);
+    }
+
+    #[test]
+    fn pam_with_alpha() {
+        let data = b"P7\nWIDTH 2\nHEIGHT 1\n# comment\nDEPTH 4\nMAXVAL 255\n\
+            TUPLTYPE RGB_ALPHA\nENDHDR\n\x01\x02\x03\x04\x05\x06\x07\x08";
+        let (pixels, alpha) = color(data);
+        assert_eq!(pixels, [1, 2, 3, 5, 6, 7]);
+        assert_eq!(alpha, Some(vec![4, 8]));
+
+        let data = b"P7\nWIDTH 1\nHEIGHT 2\nDEPTH 2\nMAXVAL 65535\n\
+            TUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\xff\xff\x00\x00\x00\x00\xff\xff";
+        let (pixels, alpha) = gray(data);
+        assert_eq!(pixels, [255, 0]);
+        assert_eq!(alpha, Some(vec![0, 255]));
+    }
+
+    #[test]
+    fn writers_round_trip() {
+        let mut frame = FrameBuffer::new(3, 2);
+        let mut alpha = StencilBuffer::new(3, 2);
+        for (i, (x, y)) in [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+            .iter()
+            .enumerate()
+        {
+            let i = i as u8;
+            frame.put_point(*x, *y, Color::rgb(i * 40, 255 - i, i * i));
+            alpha.put_point(*x, *y, i * 50);
+        }
+
+        let mut output = Vec::new();
+        frame.write(&mut output).unwrap();
+        assert_eq!(color(&output), (frame.pixels.clone(), None));
+
+        let mut output = Vec::new();
+        frame.write_pam(&mut output).unwrap();
+        assert_eq!(color(&output), (frame.pixels.clone(), None));
+
+        let mut output = Vec::new();
+        frame.write_pam_alpha(&mut output, &alpha).unwrap();
+        assert_eq!(
+            color(&output),
+            (frame.pixels.clone(), Some(alpha.pixels.clone()))
+        );
+
+        let mut output = Vec::new();
+        alpha.write_pam(&mut output).unwrap();
+        assert_eq!(gray(&output), (alpha.pixels.clone(), None));
+    }
+
+    #[test]
+    fn multiple_frames() {
+        let mut data = b"P5 1 1 255\n\x10".to_vec();
+        data.extend_from_slice(b"\nP1 2 1 01\n");
+        data.extend_from_slice(b"P6 1 1 255\n\x01\x02\x03");
+        data.extend_from_slice(b"\n\n");
+
+        let images: Vec<NetpbmImage> = NetpbmReader::new(&data[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(images.len(), 3);
+
+        let pixels: Vec<Vec<u8>> = images
+            .into_iter()
+            .map(|image| image.into_frame_buffer().pixels)
+            .collect();
+        assert_eq!(pixels[0], [16, 16, 16]);
+        assert_eq!(pixels[1], [255, 255, 255, 0, 0, 0]);
+        assert_eq!(pixels[2], [1, 2, 3]);
+    }
+
+    #[test]
+    fn reader_stops_after_error() {
+        let data = b"P5 1 1 255\n\x10P9 1 1 255\n\x10";
+        let mut reader = NetpbmReader::new(&data[..]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn truncated_data() {
+        for data in [
+            &b"P6 2 2 255\n\x00\x00\x00"[..],
+            b"P4 9 2\n\x00\x00",
+            b"P2 2 2 3\n1 2",
+        ] {
+            let error = read(data).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn huge_headers() {
+        // The size overflows, so it's rejected before anything is allocated
+        let error = read(b"P6 4000000000 4000000000 65535\n").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error =
+            read(b"P7\nWIDTH 4294967295\nHEIGHT 4294967295\nDEPTH 4\nMAXVAL 65535\nENDHDR\n")
+                .err()
+                .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // This one is allowed, but would need gigabytes to hold, so reading
+        // has to notice that the data is missing without setting aside room
+        // for all of it
+        let error = read(b"P6 37000 37000 65535\n\x00\x00").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let error = read(b"P4 1400000000 1\n\x00").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn bad_headers() {
+        for data in [
+            &b"P6 0 1 255\n"[..],
+            b"P6 1 1 0\n",
+            b"P6 1 1 65536\n",
+            b"P6 1 x 255\n",
+            b"Q6 1 1 255\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nMAXVAL 255\nENDHDR\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nMAXVAL 255\nCOLOR 3\nENDHDR\n",
+        ] {
+            let error = read(data).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", data);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use crate::curve::{flatten_cubic, flatten_quadratic, prepare_polyline, StrokeOutline};
+use crate::polygon::FillRule;
+use crate::{Canvas, Color, GraphicBuffer};
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/// A single step in drawing a path
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Segment {
+    MoveTo((f64, f64)),
+    LineTo((f64, f64)),
+    QuadTo((f64, f64), (f64, f64)),
+    CubicTo((f64, f64), (f64, f64), (f64, f64)),
+    Close,
+}
+
+/// A shape built out of lines and curves, which can be stroked or filled on a
+/// Canvas. Like an HTML canvas path, it's made up of any number of separate
+/// subpaths, each of which starts with move_to and may be closed.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Path {
+    segments: Vec<Segment>,
+    start: Option<(f64, f64)>,
+    current: Option<(f64, f64)>,
+}
+
+/// A subpath that has been flattened into straight lines
+pub(crate) struct Subpath {
+    pub points: Vec<(f64, f64)>,
+    pub closed: bool,
+}
+
+impl Path {
+    /// Creates an empty path
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    /// Checks whether anything has been added to the path
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Starts a new subpath at the given point
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.segments.push(Segment::MoveTo((x, y)));
+        self.start = Some((x, y));
+        self.current = Some((x, y));
+    }
+
+    /// Starts a new subpath at the given point if there isn't one already
+    fn ensure_subpath(&mut self, x: f64, y: f64) {
+        if self.current.is_none() {
+            self.move_to(x, y);
+        }
+    }
+
+    /// Adds a straight line from the current point to the given point
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.ensure_subpath(x, y);
+        self.segments.push(Segment::LineTo((x, y)));
+        self.current = Some((x, y));
+    }
+
+    /// Adds a quadratic Bézier curve from the current point to the given
+    /// point, bending toward the control point
+    pub fn quad_to(&mut self, cx: f64, cy: f64, x: f64, y: f64) {
+        self.ensure_subpath(cx, cy);
+        self.segments.push(Segment::QuadTo((cx, cy), (x, y)));
+        self.current = Some((x, y));
+    }
+
+    /// Adds a cubic Bézier curve from the current point to the given point,
+    /// leaving toward the first control point and arriving from the second
+    pub fn cubic_to(&mut self, cx: f64, cy: f64, cx2: f64, cy2: f64, x: f64, y: f64) {
+        self.ensure_subpath(cx, cy);
+        self.segments
+            .push(Segment::CubicTo((cx, cy), (cx2, cy2), (x, y)));
+        self.current = Some((x, y));
+    }
+
+    /// Adds part of a circle around the given center, from the start angle to
+    /// the end angle. Angles increase clockwise on the screen, and the arc
+    /// goes counter-clockwise when the end is less than the start. If there's
+    /// already a current point, a straight line connects it to the start of
+    /// the arc.
+    pub fn arc(&mut self, x: f64, y: f64, r: f64, start: f64, end: f64) {
+        let sweep = (end - start).clamp(-2.0 * PI, 2.0 * PI);
+        let (sx, sy) = (x + r * start.cos(), y + r * start.sin());
+        if self.current.is_some() {
+            self.line_to(sx, sy);
+        } else {
+            self.move_to(sx, sy);
+        }
+
+        if r > 0.0 {
+            self.unit_arc(start, sweep, |(ux, uy)| (x + r * ux, y + r * uy));
+        }
+    }
+
+    /// Adds a closed ellipse around the given center as a new subpath. The
+    /// ellipse's horizontal and vertical radii are rx and ry before it's
+    /// turned clockwise by the rotation angle.
+    pub fn ellipse(&mut self, x: f64, y: f64, rx: f64, ry: f64, rotation: f64) {
+        let (sin, cos) = rotation.sin_cos();
+        let place = |(ux, uy): (f64, f64)| {
+            let (ex, ey) = (rx * ux, ry * uy);
+            (x + ex * cos - ey * sin, y + ex * sin + ey * cos)
+        };
+
+        let (sx, sy) = place((1.0, 0.0));
+        self.move_to(sx, sy);
+        if rx > 0.0 && ry > 0.0 {
+            self.unit_arc(0.0, 2.0 * PI, place);
+        }
+        self.close();
+    }
+
+    /// Adds curves following an arc of the unit circle from the start angle
+    /// through the sweep angle, with every point moved into place by the
+    /// given function. The current point must already be at the start of the
+    /// arc.
+    fn unit_arc<T>(&mut self, start: f64, sweep: f64, place: T)
+    where
+        T: Fn((f64, f64)) -> (f64, f64),
+    {
+        /*
+        Ref: https://pomax.github.io/bezierinfo/#circles_cubic
+
+        The arc is split into pieces of at most a quarter turn, each of which
+        is closely matched by a cubic curve. For a piece that sweeps through
+        angle a, the control points lie along the tangents at either end, at
+        this distance from the endpoints:
+
+        k = 4/3 * tan(a / 4)
+
+        Scaling and rotating the control points along with the endpoints
+        gives the same kind of approximation for an ellipse.
+         */
+        if sweep == 0.0 {
+            return;
+        }
+
+        let pieces = (sweep.abs() / FRAC_PI_2).ceil() as usize;
+        let step = sweep / pieces as f64;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        for i in 0..pieces {
+            let (sin1, cos1) = (start + step * i as f64).sin_cos();
+            let (sin2, cos2) = (start + step * (i + 1) as f64).sin_cos();
+            let (cx, cy) = place((cos1 - k * sin1, sin1 + k * cos1));
+            let (cx2, cy2) = place((cos2 + k * sin2, sin2 - k * cos2));
+            let (x, y) = place((cos2, sin2));
+            self.cubic_to(cx, cy, cx2, cy2, x, y);
+        }
+    }
+
+    /// Closes the current subpath with a straight line back to its start. The
+    /// next segment continues from that starting point.
+    pub fn close(&mut self) {
+        if self.current.is_some() {
+            self.segments.push(Segment::Close);
+            self.current = self.start;
+        }
+    }
+
+    /// Flattens every subpath into straight lines, after moving each point
+    /// through the given transform
+    pub(crate) fn flatten<T>(&self, transform: T) -> Vec<Subpath>
+    where
+        T: Fn((f64, f64)) -> (f64, f64),
+    {
+        let mut subpaths = Vec::new();
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        let mut start = (0.0, 0.0);
+        let mut closed = false;
+
+        let finish = |subpaths: &mut Vec<Subpath>, points: &mut Vec<(f64, f64)>, closed| {
+            if !points.is_empty() {
+                subpaths.push(Subpath {
+                    points: std::mem::take(points),
+                    closed,
+                });
+            }
+        };
+
+        for &segment in &self.segments {
+            // Drawing after close continues a new subpath from the same
+            // starting point
+            if closed && segment != Segment::Close {
+                finish(&mut subpaths, &mut points, true);
+                closed = false;
+                if !matches!(segment, Segment::MoveTo(_)) {
+                    points.push(start);
+                }
+            }
+
+            let current = points.last().copied().unwrap_or(start);
+            match segment {
+                Segment::MoveTo(point) => {
+                    finish(&mut subpaths, &mut points, false);
+                    start = transform(point);
+                    points.push(start);
+                }
+                Segment::LineTo(point) => points.push(transform(point)),
+                Segment::QuadTo(control, end) => {
+                    flatten_quadratic(&mut points, current, transform(control), transform(end))
+                }
+                Segment::CubicTo(control, control2, end) => flatten_cubic(
+                    &mut points,
+                    current,
+                    transform(control),
+                    transform(control2),
+                    transform(end),
+                ),
+                Segment::Close => closed = true,
+            }
+        }
+
+        finish(&mut subpaths, &mut points, closed);
+        subpaths
+    }
+}
+
+/// Gathers the points of every subpath into closed contours for filling
+pub(crate) fn path_contours(subpaths: Vec<Subpath>) -> Vec<Vec<(f64, f64)>> {
+    subpaths.into_iter().map(|subpath| subpath.points).collect()
+}
+
+/// Finds the smallest box containing all the contours, as (left, top, width,
+/// height)
+fn bounds(contours: &[Vec<(f64, f64)>]) -> (f64, f64, f64, f64) {
+    let (mut left, mut top) = (f64::INFINITY, f64::INFINITY);
+    let (mut right, mut bottom) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(x, y) in contours.iter().flatten() {
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x);
+        bottom = bottom.max(y);
+    }
+    (left, top, (right - left).max(1.0), (bottom - top).max(1.0))
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Fills the inside of the path with the current fill color, using the
+    /// current fill rule. Subpaths that aren't closed are filled as if they
+    /// were.
+    pub fn fill_path(&mut self, path: &Path) {
+        let fill = self.fill;
+        self.gfill_path(path, |_, _| fill);
+    }
+
+    /// Fills the inside of the path using the given gradient(xratio, yratio),
+    /// where the ratios run across the path's bounding box
+    pub fn gfill_path<F>(&mut self, path: &Path, gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let (left, top, width, height) = bounds(&path_contours(path.flatten(|point| point)));
+        self.fill_path_with(path, |x, y| {
+            let xratio = ((x - left) / width).clamp(0.0, 1.0);
+            let yratio = ((y - top) / height).clamp(0.0, 1.0);
+            gradient(xratio, yratio)
+        });
+    }
+
+    /// Fills the inside of the path, after moving it through the current
+    /// transform, using the given color(x, y) for each pixel. The color is
+    /// given the position of the pixel before the transform, so that it lines
+    /// up with the coordinates used to build the path.
+    pub(crate) fn fill_path_with<F>(&mut self, path: &Path, color: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let contours = path_contours(path.flatten(|point| transform.apply(point)));
+        self.fill_contours(&contours, self.fill_rule, |px, py| {
+            let (x, y) = inverse.apply((px as f64, py as f64));
+            color(x, y)
+        });
+    }
+
+    /// Draws the lines and curves of the path with the current stroke color
+    /// and line width
+    pub fn stroke_path(&mut self, path: &Path) {
+        let stroke = self.stroke;
+        self.gstroke_path(path, |_| stroke);
+    }
+
+    /// Draws the lines and curves of the path using the given gradient(ratio),
+    /// where the ratio is the distance along each subpath
+    pub fn gstroke_path<F>(&mut self, path: &Path, gradient: F)
+    where
+        F: Fn(f64) -> Element,
+    {
+        self.stroke_path_with(path, self.line_width, |_, _, ratio| gradient(ratio));
+    }
+
+    /// Draws the lines and curves of the path with the given line width, after
+    /// moving them through the current transform, using the given
+    /// gradient(x, y, ratio) for each pixel. The position is the pixel's
+    /// before the transform, and the ratio is the distance along each
+    /// subpath.
+    pub(crate) fn stroke_path_with<F>(&mut self, path: &Path, width: f64, gradient: F)
+    where
+        F: Fn(f64, f64, f64) -> Element,
+    {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let width = width * transform.scale_factor();
+        for subpath in path.flatten(|point| transform.apply(point)) {
+            self.stroke_polyline(&subpath.points, subpath.closed, width, |px, py, ratio| {
+                let (x, y) = inverse.apply((px as f64, py as f64));
+                gradient(x, y, ratio)
+            });
+        }
+    }
+}
+
+impl<Buffer: GraphicBuffer<Color>> Canvas<Color, Buffer> {
+    /// Fills the inside of the path with anti-aliased edges, using the
+    /// current fill color and fill rule
+    pub fn aa_fill_path(&mut self, path: &Path) {
+        let fill = self.fill;
+        self.aa_gfill_path(path, |_, _| fill);
+    }
+
+    /// Fills the inside of the path with anti-aliased edges, using the given
+    /// gradient(xratio, yratio) across the path's bounding box
+    pub fn aa_gfill_path<F>(&mut self, path: &Path, gradient: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        let (left, top, width, height) = bounds(&path_contours(path.flatten(|point| point)));
+        self.aa_fill_path_with(path, |x, y| {
+            let xratio = ((x - left) / width).clamp(0.0, 1.0);
+            let yratio = ((y - top) / height).clamp(0.0, 1.0);
+            gradient(xratio, yratio)
+        });
+    }
+
+    /// Fills the inside of the path with anti-aliased edges, in the same way
+    /// as fill_path_with
+    pub(crate) fn aa_fill_path_with<F>(&mut self, path: &Path, color: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let contours = path_contours(path.flatten(|point| transform.apply(point)));
+        self.aa_fill_contours(&contours, self.fill_rule, |px, py| {
+            let (x, y) = inverse.apply((px as f64, py as f64));
+            color(x, y)
+        });
+    }
+
+    /// Draws the lines and curves of the path with anti-aliased edges, using
+    /// the current stroke color and line width
+    pub fn aa_stroke_path(&mut self, path: &Path) {
+        let stroke = self.stroke;
+        self.aa_stroke_path_with(path, self.line_width, |_, _| stroke);
+    }
+
+    /// Draws the lines and curves of the path with anti-aliased edges and the
+    /// given line width, after moving them through the current transform,
+    /// using the given color(x, y) for each pixel. The position is the
+    /// pixel's before the transform.
+    pub(crate) fn aa_stroke_path_with<F>(&mut self, path: &Path, width: f64, color: F)
+    where
+        F: Fn(f64, f64) -> Color,
+    {
+        /*
+        The outlines of every subpath are filled together as a single shape,
+        so that no pixel is drawn twice where the lines overlap. That's
+        important with anti-aliasing, since even solid colors are partly
+        transparent along the edges.
+         */
+        let transform = self.transform;
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let half_width = width * transform.scale_factor() / 2.0;
+        let mut contours = Vec::new();
+        for subpath in path.flatten(|point| transform.apply(point)) {
+            if let Some(points) = prepare_polyline(&subpath.points, subpath.closed) {
+                contours.extend(
+                    StrokeOutline::new(&points, subpath.closed, half_width).into_contours(),
+                );
+            }
+        }
+
+        self.aa_fill_contours(&contours, FillRule::NonZero, |px, py| {
+            let (x, y) = inverse.apply((px as f64, py as f64));
+            color(x, y)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuffer;
+
+    /// Strokes a small closed circle with a line wider than the circle, both
+    /// with and without anti-aliasing
+    fn thick_circle() -> (Canvas<Color, FrameBuffer>, Canvas<Color, FrameBuffer>) {
+        let mut path = Path::new();
+        path.arc(20.0, 20.0, 4.0, 0.0, 2.0 * PI);
+        path.close();
+
+        let black = Color::rgb(0, 0, 0);
+        let white = Color::rgb(255, 255, 255);
+        let mut plain = Canvas::new(FrameBuffer::new(40, 40), black, white);
+        let mut smooth = Canvas::new(FrameBuffer::new(40, 40), black, white);
+        for canvas in [&mut plain, &mut smooth] {
+            canvas.set_line_width(9.0);
+        }
+        plain.stroke_path(&path);
+        smooth.aa_stroke_path(&path);
+        (plain, smooth)
+    }
+
+    #[test]
+    fn thick_stroke_covers_the_center() {
+        let (plain, smooth) = thick_circle();
+        let rgb = |color: Option<Color>| color.map(|c| (c.r, c.g, c.b));
+        assert_eq!(rgb(plain.get_point(20, 20)), Some((255, 255, 255)));
+        assert_eq!(rgb(smooth.get_point(20, 20)), Some((255, 255, 255)));
+    }
+
+    #[test]
+    fn thick_stroke_matches_without_anti_aliasing() {
+        // Anywhere well inside the plain stroke has to be fully covered
+        let (plain, smooth) = thick_circle();
+        let white = |canvas: &Canvas<Color, FrameBuffer>, x, y| {
+            canvas.get_point(x, y).map(|c| (c.r, c.g, c.b)) == Some((255, 255, 255))
+        };
+        for y in 1..39 {
+            for x in 1..39 {
+                let inside = (-1..=1).all(|dy| (-1..=1).all(|dx| white(&plain, x + dx, y + dy)));
+                if inside {
+                    assert!(white(&smooth, x, y), "({}, {}) isn't covered", x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use crate::deflate;
+use crate::write_all;
+use std::io;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The PNG color types that the encoder knows how to produce
+#[derive(Clone, Copy, PartialEq)]
+pub enum PngColor {
+    Rgb,
+    Rgba,
+}
+
+impl PngColor {
+    fn channels(self) -> usize {
+        match self {
+            PngColor::Rgb => 3,
+            PngColor::Rgba => 4,
+        }
+    }
+
+    fn color_type(self) -> u8 {
+        match self {
+            PngColor::Rgb => 2,
+            PngColor::Rgba => 6,
+        }
+    }
+}
+
+const fn make_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            if c & 1 == 1 {
+                c = 0xedb88320 ^ (c >> 1);
+            } else {
+                c >>= 1;
+            }
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = make_crc_table();
+
+/// Computes the CRC-32 used to check each chunk's type and data
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = 0xffffffff;
+    for part in parts {
+        for &byte in part.iter() {
+            crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+fn write_chunk(output: &mut impl io::Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    write_all(output, &(data.len() as u32).to_be_bytes())?;
+    write_all(output, kind)?;
+    write_all(output, data)?;
+    write_all(output, &crc32(&[kind, data]).to_be_bytes())
+}
+
+/// The Paeth predictor from the PNG specification, section 9.4
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Applies one of the five filter types to a scanline, writing the filtered
+/// bytes to the output
+fn filter_row(filter: u8, row: &[u8], previous: &[u8], bpp: usize, output: &mut Vec<u8>) {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            _ => paeth(left, up, up_left),
+        };
+
+        output.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+/// Filters every scanline of the image, picking the filter for each row that
+/// minimizes the sum of absolute differences (the heuristic recommended in the
+/// PNG specification, section 12.8)
+fn filter_image(width: usize, height: usize, bpp: usize, data: &[u8]) -> Vec<u8> {
+    let stride = width * bpp;
+    let zero_row = vec![0; stride];
+
+    let mut output = Vec::with_capacity((stride + 1) * height);
+    let mut candidate = Vec::with_capacity(stride);
+    let mut best = Vec::with_capacity(stride);
+
+    for y in 0..height {
+        let row = &data[y * stride..(y + 1) * stride];
+        let previous = if y == 0 {
+            &zero_row[..]
+        } else {
+            &data[(y - 1) * stride..y * stride]
+        };
+
+        let mut best_filter = 0;
+        let mut best_score = u64::MAX;
+        for filter in 0..5 {
+            candidate.clear();
+            filter_row(filter, row, previous, bpp, &mut candidate);
+
+            let score = candidate
+                .iter()
+                .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                .sum();
+
+            if score < best_score {
+                best_score = score;
+                best_filter = filter;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        output.push(best_filter);
+        output.extend_from_slice(&best);
+    }
+
+    output
+}
+
+/// Converts the string to Latin-1, which is the only encoding that tEXt
+/// chunks allow. Returns None if any of the characters can't be encoded.
+fn latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            if (c as u32) < 256 {
+                Some(c as u8)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Builds the body of a tEXt chunk, checking it against the rules in the PNG
+/// specification, section 11.3.4
+fn text_chunk(keyword: &str, value: &str) -> io::Result<Vec<u8>> {
+    let invalid = |message| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+
+    /*
+    Keywords are 1-79 printable Latin-1 characters, where spaces are only
+    allowed one at a time between other characters
+     */
+    let keyword = match latin1(keyword) {
+        Some(keyword) => keyword,
+        None => return invalid("PNG text keywords must be Latin-1"),
+    };
+
+    if keyword.is_empty() || keyword.len() > 79 {
+        return invalid("PNG text keywords must be 1-79 characters");
+    } else if keyword.iter().any(|&c| !(32..=126).contains(&c) && c < 161) {
+        return invalid("PNG text keywords must be printable");
+    } else if keyword.starts_with(b" ")
+        || keyword.ends_with(b" ")
+        || keyword.windows(2).any(|pair| pair == b"  ")
+    {
+        return invalid("PNG text keywords can't have leading, trailing or repeated spaces");
+    }
+
+    let value = match latin1(value) {
+        Some(value) if !value.contains(&0) => value,
+        _ => return invalid("PNG text must be Latin-1 without NUL"),
+    };
+
+    let mut chunk = keyword;
+    chunk.push(0);
+    chunk.extend_from_slice(&value);
+    Ok(chunk)
+}
+
+/// Writes a complete PNG image from 8-bit samples laid out row by row. Each
+/// (keyword, text) pair is stored in its own tEXt chunk, and must be made up
+/// of Latin-1 characters.
+pub fn write_png(
+    output: &mut impl io::Write,
+    width: u32,
+    height: u32,
+    color: PngColor,
+    data: &[u8],
+    text: &[(&str, &str)],
+) -> io::Result<()> {
+    let text = text
+        .iter()
+        .map(|(keyword, value)| text_chunk(keyword, value))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    write_all(output, &SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.push(8); // Bit depth
+    header.push(color.color_type());
+    header.push(0); // Compression method
+    header.push(0); // Filter method
+    header.push(0); // Interlace method
+    write_chunk(output, b"IHDR", &header)?;
+
+    for chunk in text {
+        write_chunk(output, b"tEXt", &chunk)?;
+    }
+
+    let filtered = filter_image(width as usize, height as usize, color.channels(), data);
+    write_chunk(output, b"IDAT", &deflate::zlib_compress(&filtered))?;
+    write_chunk(output, b"IEND", &[])
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::deflate::tests::{noise, zlib_decompress};
+    use std::convert::TryInto;
+
+    /// Reverses filter_image, checking that every row uses a valid filter
+    fn unfilter_image(width: usize, height: usize, bpp: usize, data: &[u8]) -> Vec<u8> {
+        let stride = width * bpp;
+        let mut output: Vec<u8> = Vec::with_capacity(stride * height);
+        for (y, row) in data.chunks(stride + 1).enumerate() {
+            let (filter, row) = (row[0], &row[1..]);
+            assert!(filter < 5, "filter type {}", filter);
+
+            for (i, &byte) in row.iter().enumerate() {
+                let current = y * stride + i;
+                let left = if i >= bpp { output[current - bpp] } else { 0 };
+                let up = if y > 0 { output[current - stride] } else { 0 };
+                let up_left = if y > 0 && i >= bpp {
+                    output[current - stride - bpp]
+                } else {
+                    0
+                };
+
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                output.push(byte.wrapping_add(predicted));
+            }
+        }
+        output
+    }
+
+    /// Splits a PNG file into its (type, data) chunks, checking the CRCs
+    fn read_chunks(data: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(data[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut rest = &data[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+            let body = &rest[8..8 + length];
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&[&kind, body]));
+
+            chunks.push((kind, body.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    /// Decodes a PNG written by write_png, returning its width, height,
+    /// color type and unfiltered samples
+    pub(crate) fn decode_png(data: &[u8]) -> (usize, usize, u8, Vec<u8>) {
+        let chunks = read_chunks(data);
+        let header = &chunks[0].1;
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+        let bpp = match header[9] {
+            2 => 3,
+            6 => 4,
+            kind => panic!("color type {}", kind),
+        };
+
+        let compressed: Vec<u8> = chunks
+            .iter()
+            .filter(|(kind, _)| kind == b"IDAT")
+            .flat_map(|(_, body)| body.iter().copied())
+            .collect();
+        let filtered = zlib_decompress(&compressed);
+        (
+            width,
+            height,
+            header[9],
+            unfilter_image(width, height, bpp, &filtered),
+        )
+    }
+
+    #[test]
+    fn crc32_known_value() {
+        assert_eq!(crc32(&[b"123456789"]), 0xcbf43926);
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xcbf43926);
+        assert_eq!(crc32(&[b""]), 0);
+    }
+
+    #[test]
+    fn paeth_picks_closest() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(200, 100, 255), 100);
+    }
+
+    #[test]
+    fn filters_round_trip() {
+        let (width, height, bpp) = (7, 5, 3);
+        let previous = noise(width * bpp);
+        let row: Vec<u8> = noise(width * bpp * 2)[width * bpp..].to_vec();
+        for filter in 0..5 {
+            let mut filtered = vec![filter];
+            filter_row(filter, &row, &previous, bpp, &mut filtered);
+
+            let mut data = vec![0];
+            data.extend_from_slice(&previous);
+            data.extend_from_slice(&filtered);
+            let output = unfilter_image(width, 2, bpp, &data);
+            assert_eq!(output[width * bpp..], row[..], "filter {}", filter);
+        }
+
+        // Smooth gradients are better predicted by something other than None
+        let mut image = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                image.extend_from_slice(&[(x * 30) as u8, (y * 40) as u8, (x * y) as u8]);
+            }
+        }
+        let filtered = filter_image(width, height, bpp, &image);
+        assert!(filtered.chunks(width * bpp + 1).any(|row| row[0] != 0));
+        assert_eq!(unfilter_image(width, height, bpp, &filtered), image);
+    }
+
+    #[test]
+    fn rgb_image_bytes() {
+        let pixels = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        let mut output = Vec::new();
+        write_png(&mut output, 2, 2, PngColor::Rgb, &pixels, &[]).unwrap();
+
+        // Checked against an independent PNG decoder
+        let expected = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x02, 0x00, 0x00,
+            0x00, 0xfd, 0xd4, 0x9a, 0x73, 0x00, 0x00, 0x00, 0x13, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x01, 0x63, 0xf8, 0xcf, 0xc0, 0x00, 0xc4, 0x8c, 0x40, 0xe2, 0xff, 0x7f, 0x06, 0x00,
+            0x1e, 0xf6, 0x04, 0xfd, 0x3e, 0xad, 0x56, 0xde, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+            0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn rgba_image_bytes() {
+        let pixels = [
+            255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 255, 255, 255, 64,
+        ];
+        let mut output = Vec::new();
+        write_png(&mut output, 2, 2, PngColor::Rgba, &pixels, &[]).unwrap();
+
+        // Checked against an independent PNG decoder
+        let expected = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0x72, 0xb6, 0x0d, 0x24, 0x00, 0x00, 0x00, 0x15, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x01, 0x63, 0xf8, 0xcf, 0x00, 0x44, 0xff, 0x19, 0x1a, 0x18, 0xc1, 0xf4, 0x7f, 0x06,
+            0x07, 0x00, 0x3d, 0x23, 0x06, 0xbc, 0x81, 0x03, 0x65, 0x16, 0x00, 0x00, 0x00, 0x00,
+            0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn text_keywords_checked() {
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Two  spaces",
+            "Tab\tbed",
+            "Snow\u{2603}",
+        ] {
+            let error = text_chunk(keyword, "value").unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{:?}", keyword);
+        }
+
+        let long = "k".repeat(80);
+        assert!(text_chunk(&long, "value").is_err());
+        assert!(text_chunk(&long[1..], "value").is_ok());
+        assert!(text_chunk("Two spaces ok", "value").is_ok());
+    }
+
+    #[test]
+    fn text_written_as_latin1() {
+        assert_eq!(
+            text_chunk("Author", "Zo\u{eb} M\u{fc}ller\n").unwrap(),
+            b"Author\0Zo\xeb M\xfcller\n"
+        );
+        assert_eq!(text_chunk("Caf\u{e9}", "").unwrap(), b"Caf\xe9\0");
+        assert!(text_chunk("Comment", "\u{1f600}").is_err());
+        assert!(text_chunk("Comment", "nul\0byte").is_err());
+
+        // Nothing is written when the text is rejected
+        let mut output = Vec::new();
+        let text = [("Comment", "\u{1f600}")];
+        assert!(write_png(&mut output, 1, 1, PngColor::Rgb, &[0, 0, 0], &text).is_err());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let (width, height) = (33, 17);
+        let mut pixels = noise(width * height * 4 / 2);
+        for i in 0..width * height * 2 {
+            pixels.push((i % 251) as u8);
+        }
+
+        let mut output = Vec::new();
+        let text = [("Title", "Round trip"), ("Software", "framebuffer")];
+        write_png(
+            &mut output,
+            width as u32,
+            height as u32,
+            PngColor::Rgba,
+            &pixels,
+            &text,
+        )
+        .unwrap();
+
+        let chunks = read_chunks(&output);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"tEXt", b"tEXt", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[1].1, b"Title\0Round trip");
+
+        let filtered = zlib_decompress(&chunks[3].1);
+        assert_eq!(unfilter_image(width, height, 4, &filtered), pixels);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{Canvas, GraphicBuffer, Path};
+
+/// Decides which parts of a shape count as inside when its outline crosses
+/// over itself or contains other outlines
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FillRule {
+    /// A point is inside if the outline winds around it at all, in either
+    /// direction
+    NonZero,
+    /// A point is inside if a ray leaving it crosses the outline an odd
+    /// number of times, which leaves holes where outlines overlap
+    EvenOdd,
+}
+
+impl FillRule {
+    fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// A polygon edge, stored so that it always runs downward
+struct Edge {
+    top: f64,
+    bottom: f64,
+    x: f64,
+    slope: f64,
+    direction: i32,
+}
+
+/// Calls visit(py, left, right) for every run of pixels from left to right
+/// (inclusive) on row py whose centers are inside the given closed contours
+pub(crate) fn scan_contours<F>(contours: &[Vec<(f64, f64)>], rule: FillRule, mut visit: F)
+where
+    F: FnMut(i64, i64, i64),
+{
+    /*
+    Spans cover the pixels from their left end up to but not including their
+    right end, the same way that edges cover rows, so that two shapes which
+    share an edge never both draw the same pixel. A polygon with whole-number
+    corners covers the same pixels that fill_rect would.
+     */
+    scan_lines(contours, rule, 1, |py, _, left, right| {
+        let left = left.ceil() as i64;
+        let right = right.ceil() as i64 - 1;
+        if left <= right {
+            visit(py, left, right);
+        }
+    });
+}
+
+/// Splits every row of pixels into the given number of evenly spaced
+/// horizontal lines, and calls visit(py, line, left, right) for every stretch
+/// of each line that's inside the given closed contours. With a single line
+/// per row, the line runs through the centers of the pixels.
+pub(crate) fn scan_lines<F>(
+    contours: &[Vec<(f64, f64)>],
+    rule: FillRule,
+    lines: usize,
+    mut visit: F,
+) where
+    F: FnMut(i64, usize, f64, f64),
+{
+    /*
+    Every edge that crosses a line adds a crossing point, along with +1 or -1
+    depending on whether the edge goes down or up. Walking the crossings from
+    left to right and adding up those directions gives the winding number
+    between each pair of crossings, and the fill rule decides whether that
+    stretch is inside.
+
+    Edges cover the lines from their top end up to but not including their
+    bottom end, so a line passing through a corner only crosses one of the
+    two edges that meet there.
+
+    Edges are sorted by their tops, so that only the edges which could cross
+    the current line (the active edges) have to be checked.
+     */
+    let mut edges = Vec::new();
+    for contour in contours {
+        for (i, &(x1, y1)) in contour.iter().enumerate() {
+            let (x2, y2) = contour[(i + 1) % contour.len()];
+            if y1 == y2 {
+                continue;
+            }
+
+            let (top, bottom, x, direction) = if y1 < y2 {
+                (y1, y2, x1, 1)
+            } else {
+                (y2, y1, x2, -1)
+            };
+
+            edges.push(Edge {
+                top,
+                bottom,
+                x,
+                slope: (x2 - x1) / (y2 - y1),
+                direction,
+            });
+        }
+    }
+
+    if edges.is_empty() {
+        return;
+    }
+
+    edges.sort_by(|a, b| a.top.total_cmp(&b.top));
+    let first_row = edges[0].top.floor() as i64;
+    let last_row = edges
+        .iter()
+        .map(|edge| edge.bottom)
+        .fold(f64::NEG_INFINITY, f64::max)
+        .ceil() as i64;
+
+    let mut next_edge = 0;
+    let mut active: Vec<&Edge> = Vec::new();
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for py in first_row..=last_row {
+        for line in 0..lines {
+            let y = py as f64 + (line as f64 + 0.5) / lines as f64 - 0.5;
+            while next_edge < edges.len() && edges[next_edge].top <= y {
+                active.push(&edges[next_edge]);
+                next_edge += 1;
+            }
+            active.retain(|edge| edge.bottom > y);
+
+            crossings.clear();
+            for edge in &active {
+                let x = edge.x + (y - edge.top) * edge.slope;
+                crossings.push((x, edge.direction));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            let mut start = 0.0;
+            for &(x, direction) in &crossings {
+                let was_inside = rule.is_inside(winding);
+                winding += direction;
+                let is_inside = rule.is_inside(winding);
+
+                if is_inside && !was_inside {
+                    start = x;
+                } else if was_inside && !is_inside && x > start {
+                    visit(py, line, start, x);
+                }
+            }
+        }
+    }
+}
+
+/// Builds a closed path through the corners of a polygon
+fn polygon_path(points: &[(i64, i64)]) -> Path {
+    let mut path = Path::new();
+    for &(x, y) in points {
+        path.line_to(x as f64, y as f64);
+    }
+    path.close();
+    path
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Fills the given closed contours using the given color(x, y) for each
+    /// pixel
+    pub(crate) fn fill_contours<F>(
+        &mut self,
+        contours: &[Vec<(f64, f64)>],
+        rule: FillRule,
+        color: F,
+    ) where
+        F: Fn(i64, i64) -> Element,
+    {
+        scan_contours(contours, rule, |py, left, right| {
+            for px in left..=right {
+                self.put_point(px, py, color(px, py));
+            }
+        });
+    }
+
+    /// Fills the polygon with the given corners using the current fill color
+    /// and fill rule. The last corner is connected back to the first.
+    pub fn fill_polygon(&mut self, points: &[(i64, i64)]) {
+        let fill = self.fill;
+        self.fill_path_with(&polygon_path(points), |_, _| fill);
+    }
+
+    /// Fills the polygon with the given corners using the given
+    /// gradient(xratio, yratio), where the ratios run across the polygon's
+    /// bounding box
+    pub fn gfill_polygon<F>(&mut self, points: &[(i64, i64)], gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let (mut left, mut top) = (i64::MAX, i64::MAX);
+        let (mut right, mut bottom) = (i64::MIN, i64::MIN);
+        for &(x, y) in points {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+
+        let width = (right - left).max(1) as f64;
+        let height = (bottom - top).max(1) as f64;
+
+        self.fill_path_with(&polygon_path(points), |x, y| {
+            let xratio = (x - left as f64) / width;
+            let yratio = (y - top as f64) / height;
+            gradient(xratio, yratio)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StencilBuffer;
+
+    /// Counts how many times each pixel of a 40x40 area is visited while
+    /// scanning the contours
+    fn coverage(contours: &[Vec<(f64, f64)>], rule: FillRule) -> Vec<Vec<u32>> {
+        let mut counts = vec![vec![0; 40]; 40];
+        scan_contours(contours, rule, |py, left, right| {
+            for px in left..=right {
+                counts[py as usize][px as usize] += 1;
+            }
+        });
+        counts
+    }
+
+    /// Goes through the (x, y) of every pixel in the 40x40 area
+    fn every_pixel() -> impl Iterator<Item = (usize, usize)> {
+        (0..40).flat_map(|y| (0..40).map(move |x| (x, y)))
+    }
+
+    /// Turns corners into a contour
+    fn contour(points: &[(i64, i64)]) -> Vec<(f64, f64)> {
+        points.iter().map(|&(x, y)| (x as f64, y as f64)).collect()
+    }
+
+    /// A five pointed star drawn in one stroke, so that the outline crosses
+    /// itself and winds around the middle twice
+    fn star() -> Vec<(f64, f64)> {
+        contour(&[(20, 2), (31, 35), (3, 14), (37, 14), (9, 35)])
+    }
+
+    /// A square from 4 to 36 with a square hole from 14 to 26, with the hole
+    /// going either the same way as the outside or the opposite way
+    fn square_with_hole(opposite: bool) -> Vec<Vec<(f64, f64)>> {
+        let outer = contour(&[(4, 4), (36, 4), (36, 36), (4, 36)]);
+        let mut inner = contour(&[(14, 14), (26, 14), (26, 26), (14, 26)]);
+        if opposite {
+            inner.reverse();
+        }
+        vec![outer, inner]
+    }
+
+    #[test]
+    fn rectangle_matches_fill_rect() {
+        let mut polygon = Canvas::new(StencilBuffer::new(12, 10), 1, 1);
+        polygon.fill_polygon(&[(2, 3), (7, 3), (7, 8), (2, 8)]);
+
+        let mut rect = Canvas::new(StencilBuffer::new(12, 10), 1, 1);
+        rect.fill_rect(2, 3, 5, 5);
+        for y in 0..10 {
+            for x in 0..12 {
+                let inside = (2..7).contains(&x) && (3..8).contains(&y);
+                assert_eq!(
+                    polygon.get_point(x, y),
+                    Some(inside as u8),
+                    "({}, {})",
+                    x,
+                    y
+                );
+                assert_eq!(rect.get_point(x, y), Some(inside as u8), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn star_fill_rules() {
+        let non_zero = coverage(&[star()], FillRule::NonZero);
+        let even_odd = coverage(&[star()], FillRule::EvenOdd);
+
+        // The middle is wound around twice
+        assert_eq!(non_zero[20][20], 1);
+        assert_eq!(even_odd[20][20], 0);
+
+        // The points are only wound around once
+        for &(x, y) in [(20, 6), (33, 15), (8, 15), (29, 31), (11, 31)].iter() {
+            assert_eq!(non_zero[y][x], 1, "({}, {})", x, y);
+            assert_eq!(even_odd[y][x], 1, "({}, {})", x, y);
+        }
+
+        // Between the points is outside for both
+        for &(x, y) in [(2, 2), (20, 33), (33, 25), (7, 25), (10, 8)].iter() {
+            assert_eq!(non_zero[y][x], 0, "({}, {})", x, y);
+            assert_eq!(even_odd[y][x], 0, "({}, {})", x, y);
+        }
+
+        // Even-odd only ever leaves out pixels that non-zero fills
+        for (x, y) in every_pixel() {
+            assert!(even_odd[y][x] <= non_zero[y][x]);
+        }
+    }
+
+    #[test]
+    fn hole_fill_rules() {
+        for &opposite in [false, true].iter() {
+            let contours = square_with_hole(opposite);
+            for &rule in [FillRule::NonZero, FillRule::EvenOdd].iter() {
+                let counts = coverage(&contours, rule);
+                let hole = opposite || rule == FillRule::EvenOdd;
+                for (x, y) in every_pixel() {
+                    let in_outer = (4..36).contains(&x) && (4..36).contains(&y);
+                    let in_inner = (14..26).contains(&x) && (14..26).contains(&y);
+                    let expected = in_outer && !(hole && in_inner);
+                    assert_eq!(
+                        counts[y][x], expected as u32,
+                        "({}, {}) {:?} opposite {}",
+                        x, y, rule, opposite
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_drawn_once() {
+        // Two triangles splitting a square along a slanted line, and a third
+        // next to them along a straight one, each filled on its own
+        let contours = [
+            contour(&[(3, 3), (30, 3), (3, 25)]),
+            contour(&[(30, 3), (30, 25), (3, 25)]),
+            contour(&[(30, 3), (38, 3), (30, 25)]),
+        ];
+        let mut counts = vec![vec![0; 40]; 40];
+        for contour in contours.iter() {
+            let single = coverage(std::slice::from_ref(contour), FillRule::NonZero);
+            for (row, single) in counts.iter_mut().zip(single) {
+                for (count, single) in row.iter_mut().zip(single) {
+                    *count += single;
+                }
+            }
+        }
+
+        for (x, y) in every_pixel() {
+            let in_square = (3..30).contains(&x) && (3..25).contains(&y);
+            if in_square {
+                assert_eq!(counts[y][x], 1, "({}, {})", x, y);
+            } else {
+                assert!(counts[y][x] <= 1, "({}, {})", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use crate::{Color, FrameBuffer};
+use std::collections::HashMap;
+
+/// The algorithms available for reducing an image to a limited palette
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuantizeMethod {
+    /// Repeatedly splits the color box with the widest range at its median
+    MedianCut,
+    /// Merges the least used branches of an RGB octree
+    Octree,
+    /// Refines clusters of similar colors with Lloyd's algorithm, starting
+    /// from k-means++ seeds
+    KMeans,
+}
+
+/// An image reduced to a palette, along with the palette index of every pixel
+/// in row-major order
+pub struct Quantized {
+    pub palette: Vec<Color>,
+    pub indexes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Quantized {
+    /// Renders the indexed image back into a FrameBuffer using its palette
+    pub fn to_frame_buffer(&self) -> FrameBuffer {
+        let mut pixels = Vec::with_capacity(self.indexes.len() * 3);
+        for &index in self.indexes.iter() {
+            let color = self.palette[index as usize];
+            pixels.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+
+        FrameBuffer {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+/// Reduces the buffer to at most the given number of colors (between 1 and
+/// 256). The seed only affects k-means, but the result is the same for the
+/// same seed and input regardless of method.
+pub fn quantize(
+    buffer: &FrameBuffer,
+    colors: usize,
+    method: QuantizeMethod,
+    seed: u64,
+) -> Quantized {
+    let colors = colors.clamp(1, 256);
+    let histogram = histogram(&buffer.pixels);
+    let palette = match method {
+        QuantizeMethod::MedianCut => median_cut(&histogram, colors),
+        QuantizeMethod::Octree => octree(&histogram, colors),
+        QuantizeMethod::KMeans => kmeans(&histogram, colors, seed),
+    };
+
+    let mut mapping = PaletteMap::new(&palette);
+    let indexes = buffer
+        .pixels
+        .chunks(3)
+        .map(|pixel| mapping.index([pixel[0], pixel[1], pixel[2]]))
+        .collect();
+
+    Quantized {
+        palette,
+        indexes,
+        width: buffer.width,
+        height: buffer.height,
+    }
+}
+
+/// A distinct color and the number of pixels that use it
+type Bin = ([u8; 3], u32);
+
+/// Counts how many times each distinct color appears in a run of packed RGB
+/// samples
+pub fn histogram(pixels: &[u8]) -> Vec<Bin> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in pixels.chunks(3) {
+        *counts.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+    }
+
+    let mut bins: Vec<Bin> = counts.into_iter().collect();
+    // HashMap iteration order is random, sort so the output is reproducible
+    bins.sort_unstable();
+    bins
+}
+
+/// Returns the (min, max) of each channel within the bins
+fn bounds(bins: &[Bin]) -> [(u8, u8); 3] {
+    let mut bounds = [(255, 0); 3];
+    for (color, _) in bins {
+        for channel in 0..3 {
+            bounds[channel].0 = bounds[channel].0.min(color[channel]);
+            bounds[channel].1 = bounds[channel].1.max(color[channel]);
+        }
+    }
+    bounds
+}
+
+/// Averages the colors in the bins, weighted by how often they appear
+fn weighted_mean(bins: &[Bin]) -> Color {
+    let mut sums = [0u64; 3];
+    let mut total = 0u64;
+    for (color, count) in bins {
+        for channel in 0..3 {
+            sums[channel] += color[channel] as u64 * *count as u64;
+        }
+        total += *count as u64;
+    }
+
+    let average = |sum: u64| ((sum + total / 2) / total) as u8;
+    Color::rgb(average(sums[0]), average(sums[1]), average(sums[2]))
+}
+
+/// Reduces the histogram to at most the given number of colors using
+/// Heckbert's median cut. If there are already few enough distinct colors they
+/// are returned as-is.
+pub fn median_cut(histogram: &[Bin], colors: usize) -> Vec<Color> {
+    if histogram.len() <= colors {
+        return histogram
+            .iter()
+            .map(|(color, _)| Color::rgb(color[0], color[1], color[2]))
+            .collect();
+    }
+
+    /*
+    Each box starts out holding every color. On each round the box with the
+    widest channel range is sorted along that channel and split at the point
+    where half of its pixels (not half of its distinct colors) fall on either
+    side, until there are as many boxes as requested colors.
+     */
+    let mut boxes = vec![(bounds(histogram), histogram.to_vec())];
+    while boxes.len() < colors {
+        let mut widest = None;
+        let mut widest_range = 0;
+        for (index, (bounds, bins)) in boxes.iter().enumerate() {
+            if bins.len() < 2 {
+                continue;
+            }
+
+            for (channel, (min, max)) in bounds.iter().enumerate() {
+                let range = (max - min) as u32 + 1;
+                if range > widest_range {
+                    widest = Some((index, channel));
+                    widest_range = range;
+                }
+            }
+        }
+
+        let (index, channel) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+
+        let (_, mut bins) = boxes.swap_remove(index);
+        bins.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        let total: u64 = bins.iter().map(|(_, count)| *count as u64).sum();
+        let mut seen = 0;
+        let mut split = 1;
+        for (offset, (_, count)) in bins.iter().enumerate() {
+            seen += *count as u64;
+            if seen * 2 >= total {
+                split = (offset + 1).clamp(1, bins.len() - 1);
+                break;
+            }
+        }
+
+        let upper = bins.split_off(split);
+        boxes.push((bounds(&bins), bins));
+        boxes.push((bounds(&upper), upper));
+    }
+
+    boxes.iter().map(|(_, bins)| weighted_mean(bins)).collect()
+}
+
+/// A node in the octree, whose children are indexes into the tree's node list
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    sums: [u64; 3],
+    count: u64,
+    leaf: bool,
+}
+
+/// Reduces the histogram to at most the given number of colors by building an
+/// octree and folding the least populated branches into their parents
+pub fn octree(histogram: &[Bin], colors: usize) -> Vec<Color> {
+    /*
+    Each level of the tree picks a child using one bit from each channel,
+    starting at the most significant, so the leaves at depth 8 are single
+    colors. Every node keeps the total of all the colors below it. Reducing a
+    node turns it into a leaf with the average of its children, and we always
+    reduce the deepest nodes first (fewest pixels first within a level) since
+    their children are the most similar.
+     */
+    let mut nodes = vec![OctreeNode {
+        children: [None; 8],
+        sums: [0; 3],
+        count: 0,
+        leaf: false,
+    }];
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); 8];
+    levels[0].push(0);
+    let mut leaves = 0;
+
+    for (color, count) in histogram {
+        let mut node = 0;
+        for depth in 0..=8 {
+            nodes[node].count += *count as u64;
+            for (sum, &value) in nodes[node].sums.iter_mut().zip(color.iter()) {
+                *sum += value as u64 * *count as u64;
+            }
+
+            if depth == 8 {
+                break;
+            }
+
+            let shift = 7 - depth;
+            let child = (((color[0] >> shift) & 1) << 2
+                | ((color[1] >> shift) & 1) << 1
+                | ((color[2] >> shift) & 1)) as usize;
+
+            node = match nodes[node].children[child] {
+                Some(next) => next,
+                None => {
+                    let next = nodes.len();
+                    nodes.push(OctreeNode {
+                        children: [None; 8],
+                        sums: [0; 3],
+                        count: 0,
+                        leaf: depth == 7,
+                    });
+                    nodes[node].children[child] = Some(next);
+                    if depth == 7 {
+                        leaves += 1;
+                    } else {
+                        levels[depth + 1].push(next);
+                    }
+                    next
+                }
+            };
+        }
+    }
+
+    for depth in (0..8).rev() {
+        if leaves <= colors {
+            break;
+        }
+
+        let mut candidates = levels[depth].clone();
+        candidates.sort_by_key(|&node| (nodes[node].count, node));
+        for node in candidates {
+            if leaves <= colors {
+                break;
+            }
+
+            let children = nodes[node].children.iter().flatten().count();
+            nodes[node].children = [None; 8];
+            nodes[node].leaf = true;
+            leaves = leaves + 1 - children;
+        }
+    }
+
+    let mut palette = Vec::with_capacity(leaves);
+    let mut stack = vec![0];
+    while let Some(node) = stack.pop() {
+        let node = &nodes[node];
+        if node.leaf {
+            let average =
+                |channel: usize| ((node.sums[channel] + node.count / 2) / node.count) as u8;
+            palette.push(Color::rgb(average(0), average(1), average(2)));
+        } else {
+            stack.extend(node.children.iter().rev().flatten());
+        }
+    }
+    palette
+}
+
+/// A xorshift64* generator, used so that k-means gives the same result for the
+/// same seed without depending on an external crate
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Returns a value in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn distance_squared(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+/// The channel totals and pixel count of the colors that fall into one cell of
+/// a coarser color grid
+type Cell = ([u64; 3], u64);
+
+/// Reduces the histogram to at most the given number of colors using k-means
+/// clustering with k-means++ initialization
+pub fn kmeans(histogram: &[Bin], colors: usize, seed: u64) -> Vec<Color> {
+    if histogram.len() <= colors {
+        return median_cut(histogram, colors);
+    }
+
+    // Clustering every distinct color of a noisy image is slow, so merge them
+    // into a grid with 5 bits per channel first. Each cell keeps the average
+    // of its colors, so little precision is lost.
+    let mut cells: HashMap<[u8; 3], Cell> = HashMap::new();
+    for (color, count) in histogram {
+        let cell = cells
+            .entry([color[0] >> 3, color[1] >> 3, color[2] >> 3])
+            .or_insert(([0; 3], 0));
+        for (sum, &value) in cell.0.iter_mut().zip(color.iter()) {
+            *sum += value as u64 * *count as u64;
+        }
+        cell.1 += *count as u64;
+    }
+
+    let mut cells: Vec<([u8; 3], Cell)> = cells.into_iter().collect();
+    cells.sort_unstable_by_key(|(key, _)| *key);
+    let points: Vec<([f64; 3], f64)> = cells
+        .iter()
+        .map(|(_, (sums, count))| {
+            let count = *count as f64;
+            (
+                [
+                    sums[0] as f64 / count,
+                    sums[1] as f64 / count,
+                    sums[2] as f64 / count,
+                ],
+                count,
+            )
+        })
+        .collect();
+
+    let mut rng = Xorshift(seed ^ 0x9e3779b97f4a7c15);
+    if rng.0 == 0 {
+        rng.0 = 1;
+    }
+
+    // k-means++: each new center is picked with probability proportional to
+    // its (weighted) squared distance from the closest existing center
+    let mut centers = vec![points[(rng.next() % points.len() as u64) as usize].0];
+    let mut nearest: Vec<f64> = points
+        .iter()
+        .map(|(point, _)| distance_squared(point, &centers[0]))
+        .collect();
+
+    while centers.len() < colors.min(points.len()) {
+        let total: f64 = points
+            .iter()
+            .zip(nearest.iter())
+            .map(|((_, weight), distance)| weight * distance)
+            .sum();
+        if total <= 0.0 {
+            break;
+        }
+
+        let mut target = rng.next_f64() * total;
+        let mut chosen = points.len() - 1;
+        for (index, ((_, weight), distance)) in points.iter().zip(nearest.iter()).enumerate() {
+            target -= weight * distance;
+            if target <= 0.0 {
+                chosen = index;
+                break;
+            }
+        }
+
+        let center = points[chosen].0;
+        for ((point, _), distance) in points.iter().zip(nearest.iter_mut()) {
+            *distance = distance.min(distance_squared(point, &center));
+        }
+        centers.push(center);
+    }
+
+    let mut assignments = vec![0; points.len()];
+    for _ in 0..32 {
+        let mut moved = false;
+        for ((point, _), assignment) in points.iter().zip(assignments.iter_mut()) {
+            let mut best = 0;
+            let mut best_distance = f64::MAX;
+            for (index, center) in centers.iter().enumerate() {
+                let distance = distance_squared(point, center);
+                if distance < best_distance {
+                    best = index;
+                    best_distance = distance;
+                }
+            }
+
+            if *assignment != best {
+                *assignment = best;
+                moved = true;
+            }
+        }
+
+        let mut sums = vec![([0.0; 3], 0.0); centers.len()];
+        for ((point, weight), &assignment) in points.iter().zip(assignments.iter()) {
+            for (sum, value) in sums[assignment].0.iter_mut().zip(point.iter()) {
+                *sum += value * weight;
+            }
+            sums[assignment].1 += weight;
+        }
+
+        for (center, (sum, weight)) in centers.iter_mut().zip(sums.iter()) {
+            if *weight > 0.0 {
+                *center = [sum[0] / weight, sum[1] / weight, sum[2] / weight];
+            }
+        }
+
+        if !moved {
+            break;
+        }
+    }
+
+    centers
+        .iter()
+        .map(|center| {
+            Color::rgb(
+                center[0].round() as u8,
+                center[1].round() as u8,
+                center[2].round() as u8,
+            )
+        })
+        .collect()
+}
+
+/// Finds the palette entry closest to the given color, caching the results
+/// since most images reuse the same colors many times
+pub struct PaletteMap<'a> {
+    palette: &'a [Color],
+    cache: HashMap<[u8; 3], u8>,
+}
+
+impl<'a> PaletteMap<'a> {
+    pub fn new(palette: &'a [Color]) -> PaletteMap<'a> {
+        PaletteMap {
+            palette,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Gets the index of the nearest palette color by Euclidean distance
+    pub fn index(&mut self, color: [u8; 3]) -> u8 {
+        let palette = self.palette;
+        *self.cache.entry(color).or_insert_with(|| {
+            let distance = |entry: &Color| {
+                let dr = entry.r as i32 - color[0] as i32;
+                let dg = entry.g as i32 - color[1] as i32;
+                let db = entry.b as i32 - color[2] as i32;
+                dr * dr + dg * dg + db * db
+            };
+
+            palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| distance(entry))
+                .map(|(index, _)| index as u8)
+                .unwrap_or(0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GraphicBuffer;
+
+    const METHODS: [QuantizeMethod; 3] = [
+        QuantizeMethod::MedianCut,
+        QuantizeMethod::Octree,
+        QuantizeMethod::KMeans,
+    ];
+
+    /// Builds an image where nearly every pixel is a different color
+    fn noisy_image() -> FrameBuffer {
+        let mut buffer = FrameBuffer::new(40, 30);
+        let mut state: u32 = 0x1234567;
+        for y in 0..30 {
+            for x in 0..40 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let [r, g, b, _] = state.to_le_bytes();
+                buffer.put_point(x, y, Color::rgb(r, g, b));
+            }
+        }
+        buffer
+    }
+
+    /// Builds an image out of the given colors, in uneven amounts
+    fn few_colors(colors: &[Color]) -> FrameBuffer {
+        let mut buffer = FrameBuffer::new(40, 30);
+        for y in 0..30 {
+            for x in 0..40 {
+                let index = (x * x + y * 3) as usize % colors.len();
+                buffer.put_point(x, y, colors[index]);
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn palette_fits_the_limit() {
+        let buffer = noisy_image();
+        for &method in METHODS.iter() {
+            for colors in [1, 2, 7, 16, 100, 255, 256] {
+                let quantized = quantize(&buffer, colors, method, 7);
+                assert!(!quantized.palette.is_empty());
+                assert!(
+                    quantized.palette.len() <= colors,
+                    "{} colors for a limit of {}",
+                    quantized.palette.len(),
+                    colors
+                );
+                assert!(quantized
+                    .indexes
+                    .iter()
+                    .all(|&index| (index as usize) < quantized.palette.len()));
+            }
+
+            // Limits outside of 1-256 are clamped
+            assert_eq!(quantize(&buffer, 0, method, 7).palette.len(), 1);
+            assert!(quantize(&buffer, 1000, method, 7).palette.len() <= 256);
+        }
+    }
+
+    #[test]
+    fn few_colors_reproduced_exactly() {
+        let colors: Vec<Color> = (0..12u8)
+            .map(|i| Color::rgb(i * 20, 255 - i * 15, i.wrapping_mul(97)))
+            .chain([Color::black(), Color::white(), Color::rgb(1, 0, 0)])
+            .collect();
+        let buffer = few_colors(&colors);
+
+        for &method in METHODS.iter() {
+            for limit in [colors.len(), 64, 256] {
+                let quantized = quantize(&buffer, limit, method, 3);
+                assert_eq!(quantized.width, 40);
+                assert_eq!(quantized.height, 30);
+                assert!(quantized.to_frame_buffer().pixels == buffer.pixels);
+            }
+        }
+    }
+
+    #[test]
+    fn single_color() {
+        let buffer = few_colors(&[Color::rgb(10, 20, 30)]);
+        for &method in METHODS.iter() {
+            let quantized = quantize(&buffer, 16, method, 0);
+            assert_eq!(quantized.palette.len(), 1);
+            assert!(quantized.to_frame_buffer().pixels == buffer.pixels);
+        }
+    }
+
+    #[test]
+    fn kmeans_is_repeatable() {
+        let buffer = noisy_image();
+        let first = quantize(&buffer, 16, QuantizeMethod::KMeans, 42);
+        let second = quantize(&buffer, 16, QuantizeMethod::KMeans, 42);
+        assert!(first.palette == second.palette);
+        assert_eq!(first.indexes, second.indexes);
+
+        let histogram = histogram(&buffer.pixels);
+        assert!(kmeans(&histogram, 8, 9) == kmeans(&histogram, 8, 9));
+    }
+
+    #[test]
+    fn maps_to_nearest_color() {
+        let palette = [Color::black(), Color::rgb(255, 0, 0), Color::white()];
+        let mut mapping = PaletteMap::new(&palette);
+        assert_eq!(mapping.index([10, 5, 5]), 0);
+        assert_eq!(mapping.index([200, 40, 30]), 1);
+        assert_eq!(mapping.index([230, 240, 250]), 2);
+        assert_eq!(mapping.index([200, 40, 30]), 1);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{Canvas, Color, FrameBuffer, GraphicBuffer, RgbaFrameBuffer};
+use std::f64::consts::PI;
+
+/// Decides how the pixels of an image are mixed together when it's resized
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    /// Every pixel takes the color of the closest source pixel. This keeps
+    /// hard edges, so it's the one to use for blowing up pixel art.
+    Nearest,
+    /// Mixes the nearest two source pixels in each direction
+    Bilinear,
+    /// Fits a Catmull-Rom curve through the nearest four source pixels in
+    /// each direction, which is sharper than bilinear
+    Bicubic,
+    /// Uses a windowed sinc over the nearest six source pixels in each
+    /// direction. This is the sharpest, but can leave faint ripples next to
+    /// hard edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// Gets how many source pixels away from the center the filter reaches,
+    /// at the source's own scale
+    fn radius(self) -> f64 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    /// Gets how much a source pixel at the given distance from the center
+    /// counts towards the result
+    fn weight(self, distance: f64) -> f64 {
+        let x = distance.abs();
+        match self {
+            Filter::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                /*
+                Ref: Keys, "Cubic Convolution Interpolation for Digital Image
+                Processing", 1981, with a = -0.5
+                 */
+                if x < 1.0 {
+                    (1.5 * x - 2.5) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos => {
+                if x == 0.0 {
+                    1.0
+                } else if x < 3.0 {
+                    let x = x * PI;
+                    3.0 * x.sin() * (x / 3.0).sin() / (x * x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Works out which source pixels go into each of the target pixels along
+    /// one axis, as (source index, weight) pairs
+    fn taps(self, source: i64, target: i64) -> Vec<Vec<(usize, f32)>> {
+        let scale = source as f64 / target as f64;
+
+        /*
+        When shrinking, each target pixel covers several source pixels. The
+        filter is stretched out to cover all of them, or else the pixels in
+        between would be skipped and the result would alias.
+         */
+        let stretch = scale.max(1.0);
+        let reach = self.radius() * stretch;
+
+        (0..target)
+            .map(|i| {
+                let center = (i as f64 + 0.5) * scale - 0.5;
+                if self == Filter::Nearest {
+                    let index = (center + 0.5).floor().clamp(0.0, (source - 1) as f64);
+                    return vec![(index as usize, 1.0)];
+                }
+
+                let first = (center - reach).ceil() as i64;
+                let last = (center + reach).floor() as i64;
+                let mut taps: Vec<(usize, f32)> = Vec::new();
+                let mut total = 0.0;
+                for j in first..=last {
+                    let weight = self.weight((j as f64 - center) / stretch);
+                    if weight == 0.0 {
+                        continue;
+                    }
+
+                    // Pixels past the edges repeat the ones on the edges
+                    taps.push((j.clamp(0, source - 1) as usize, weight as f32));
+                    total += weight;
+                }
+
+                if total != 0.0 {
+                    for tap in taps.iter_mut() {
+                        tap.1 /= total as f32;
+                    }
+                }
+                taps
+            })
+            .collect()
+    }
+}
+
+/// Resizes the (x, y, width, height) region of the source to the given size,
+/// returning the result as premultiplied RGBA samples. Parts of the region
+/// outside of the source are transparent.
+pub(crate) fn resample<Source>(
+    source: &Source,
+    region: (i64, i64, i64, i64),
+    width: u32,
+    height: u32,
+    filter: Filter,
+) -> Vec<u8>
+where
+    Source: GraphicBuffer<Color>,
+{
+    let (left, top, source_width, source_height) = region;
+    let (width, height) = (width as usize, height as usize);
+    if source_width <= 0 || source_height <= 0 {
+        return vec![0; width * height * 4];
+    }
+
+    /*
+    The colors are mixed premultiplied, so that a transparent pixel doesn't
+    drag the color of its neighbors towards whatever color it happens to
+    have.
+     */
+    let mut pixels = Vec::with_capacity((source_width * source_height) as usize);
+    for y in top..top + source_height {
+        for x in left..left + source_width {
+            let color = source.get_point(x, y).unwrap_or(Color::rgba(0, 0, 0, 0));
+            let alpha = color.alpha as f32 / 255.0;
+            pixels.push([
+                color.r as f32 * alpha,
+                color.g as f32 * alpha,
+                color.b as f32 * alpha,
+                color.alpha as f32,
+            ]);
+        }
+    }
+
+    // Resizing is done one direction at a time, rows first and then columns
+    let columns = filter.taps(source_width, width as i64);
+    let rows = filter.taps(source_height, height as i64);
+
+    let source_width = source_width as usize;
+    let mut across = Vec::with_capacity(width * source_height as usize);
+    for row in pixels.chunks(source_width) {
+        for taps in columns.iter() {
+            across.push(mix(taps.iter().map(|&(i, weight)| (row[i], weight))));
+        }
+    }
+
+    let mut result = Vec::with_capacity(width * height * 4);
+    for taps in rows.iter() {
+        for x in 0..width {
+            let [r, g, b, alpha] = mix(taps
+                .iter()
+                .map(|&(i, weight)| (across[i * width + x], weight)));
+
+            // Sharper filters can overshoot, which has to be clipped back
+            // into range, without letting the colors outgrow the alpha
+            let alpha = alpha.round().clamp(0.0, 255.0);
+            let channel = |value: f32| value.round().clamp(0.0, alpha) as u8;
+            result.extend_from_slice(&[channel(r), channel(g), channel(b), alpha as u8]);
+        }
+    }
+
+    result
+}
+
+/// Adds up the weighted samples
+fn mix(samples: impl Iterator<Item = ([f32; 4], f32)>) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (sample, weight) in samples {
+        for (total, value) in sum.iter_mut().zip(sample.iter()) {
+            *total += value * weight;
+        }
+    }
+    sum
+}
+
+impl FrameBuffer {
+    /// Creates a copy of the framebuffer stretched or shrunk to the given size
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> FrameBuffer {
+        let region = (0, 0, self.width as i64, self.height as i64);
+        let pixels = resample(self, region, width, height, filter)
+            .chunks(4)
+            .flat_map(|pixel| pixel[..3].iter().copied())
+            .collect();
+
+        FrameBuffer {
+            pixels,
+            width,
+            height,
+        }
+    }
+}
+
+impl<Buffer: GraphicBuffer<Color>> Canvas<Color, Buffer> {
+    /// Copies the (x, y, width, height) region of the source buffer onto the
+    /// (x, y, width, height) area of the canvas, resizing it with the filter.
+    /// The resized pixels are then drawn the same way as blit_region draws
+    /// them.
+    pub fn blit_scaled<Source>(
+        &mut self,
+        source: &Source,
+        region: (i64, i64, i64, i64),
+        target: (i64, i64, i64, i64),
+        filter: Filter,
+    ) where
+        Source: GraphicBuffer<Color>,
+    {
+        let (x, y, width, height) = target;
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let scaled =
+            RgbaFrameBuffer::resampled(source, region, width as u32, height as u32, filter);
+        self.blit(&scaled, x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Lanczos,
+    ];
+
+    /// Builds an image with a different, partly transparent color in every
+    /// pixel
+    fn noisy_image(width: u32, height: u32) -> RgbaFrameBuffer {
+        let mut buffer = RgbaFrameBuffer::new(width, height);
+        let mut state: u32 = 0x2545f491;
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let [r, g, b, alpha] = state.to_le_bytes();
+                buffer.put_point(x, y, Color::rgba(r, g, b, alpha | 1));
+            }
+        }
+        buffer
+    }
+
+    /// Lists the channels of every pixel in the buffer
+    fn channels<Buffer: GraphicBuffer<Color>>(buffer: &Buffer) -> Vec<[u8; 4]> {
+        let mut channels = Vec::new();
+        for y in 0..buffer.height() as i64 {
+            for x in 0..buffer.width() as i64 {
+                let color = buffer.get_point(x, y).unwrap();
+                channels.push([color.r, color.g, color.b, color.alpha]);
+            }
+        }
+        channels
+    }
+
+    #[test]
+    fn same_size_is_unchanged() {
+        let image = noisy_image(13, 9);
+        let mut opaque = FrameBuffer::new(13, 9);
+        for (i, pixel) in channels(&image).into_iter().enumerate() {
+            let [r, g, b, _] = pixel;
+            opaque.put_point(i as i64 % 13, i as i64 / 13, Color::rgb(r, g, b));
+        }
+
+        for &filter in FILTERS.iter() {
+            let resized = image.resize(13, 9, filter);
+            assert!(channels(&resized) == channels(&image), "{:?}", filter);
+
+            let resized = opaque.resize(13, 9, filter);
+            assert!(channels(&resized) == channels(&opaque), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn constant_image_stays_constant() {
+        let color = Color::rgba(200, 90, 17, 160);
+        let mut image = RgbaFrameBuffer::new(10, 8);
+        for y in 0..8 {
+            for x in 0..10 {
+                image.put_point(x, y, color);
+            }
+        }
+        let expected = image.get_point(0, 0).unwrap();
+        let expected = [expected.r, expected.g, expected.b, expected.alpha];
+
+        for &filter in FILTERS.iter() {
+            for &(width, height) in [(10, 8), (37, 29), (3, 2), (1, 1), (25, 3)].iter() {
+                let resized = image.resize(width, height, filter);
+                for pixel in channels(&resized) {
+                    assert!(
+                        pixel == expected,
+                        "{:?} at {}x{}: {:?}",
+                        filter,
+                        width,
+                        height,
+                        pixel
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn outside_the_source_is_transparent() {
+        let image = noisy_image(4, 4);
+        let pixels = resample(&image, (10, 10, 4, 4), 6, 6, Filter::Lanczos);
+        assert!(pixels.iter().all(|&sample| sample == 0));
+
+        let pixels = resample(&image, (0, 0, 0, 4), 3, 3, Filter::Bicubic);
+        assert_eq!(pixels, vec![0; 3 * 3 * 4]);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::resample::resample;
+use crate::{
+    png, read_netpbm, write_all, BlendMode, Color, Filter, FrameBuffer, GraphicBuffer, NetpbmImage,
+    StencilBuffer,
+};
+use std::io;
+
+/// Multiplies two channel values as if they were fractions of 255, rounding to
+/// the nearest value
+fn mul(a: u8, b: u8) -> u8 {
+    ((a as u16 * b as u16 + 127) / 255) as u8
+}
+
+/// A graphical buffer containing pixel colors along with how opaque each pixel
+/// is. Unlike FrameBuffer, drawing a transparent color onto a transparent
+/// pixel leaves it partly transparent.
+pub struct RgbaFrameBuffer {
+    /// RGBA samples, with the color channels already multiplied by the alpha.
+    /// This keeps compositing down to a single multiply-add per channel, and
+    /// makes fully transparent pixels all the same regardless of their color.
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl RgbaFrameBuffer {
+    /// Creates a new RgbaFrameBuffer that's completely transparent
+    pub fn new(width: u32, height: u32) -> RgbaFrameBuffer {
+        RgbaFrameBuffer {
+            pixels: vec![0; (width * height * 4) as usize],
+            width,
+            height,
+        }
+    }
+
+    /// Creates a new RgbaFrameBuffer with the same pixels as the framebuffer,
+    /// all of them fully opaque
+    pub fn from_frame_buffer(frame: &FrameBuffer) -> RgbaFrameBuffer {
+        RgbaFrameBuffer::from_parts(frame, None)
+    }
+
+    /// Creates a new RgbaFrameBuffer with the colors from the framebuffer and
+    /// the alpha values from the stencil. The stencil must be the same size as
+    /// the framebuffer.
+    pub fn with_alpha(frame: &FrameBuffer, alpha: &StencilBuffer) -> io::Result<RgbaFrameBuffer> {
+        if alpha.width != frame.width || alpha.height != frame.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "alpha stencil does not match the framebuffer size",
+            ));
+        }
+
+        Ok(RgbaFrameBuffer::from_parts(frame, Some(alpha)))
+    }
+
+    /// Combines the colors and alpha values, which must be the same size
+    pub(crate) fn from_parts(
+        frame: &FrameBuffer,
+        alpha: Option<&StencilBuffer>,
+    ) -> RgbaFrameBuffer {
+        let mut pixels = Vec::with_capacity(frame.pixels.len() / 3 * 4);
+        for (i, color) in frame.pixels.chunks(3).enumerate() {
+            let alpha = alpha.map_or(255, |alpha| alpha.pixels[i]);
+            pixels.extend_from_slice(&[
+                mul(color[0], alpha),
+                mul(color[1], alpha),
+                mul(color[2], alpha),
+                alpha,
+            ]);
+        }
+
+        RgbaFrameBuffer {
+            pixels,
+            width: frame.width,
+            height: frame.height,
+        }
+    }
+
+    /// Resizes the (x, y, width, height) region of the source to the given
+    /// size
+    pub(crate) fn resampled<Source>(
+        source: &Source,
+        region: (i64, i64, i64, i64),
+        width: u32,
+        height: u32,
+        filter: Filter,
+    ) -> RgbaFrameBuffer
+    where
+        Source: GraphicBuffer<Color>,
+    {
+        RgbaFrameBuffer {
+            pixels: resample(source, region, width, height, filter),
+            width,
+            height,
+        }
+    }
+
+    /// Reads a NetPBM image of any format from the stream. Grayscale images
+    /// are expanded to RGB, and images without an alpha channel are fully
+    /// opaque.
+    pub fn read(input: &mut impl io::BufRead) -> io::Result<RgbaFrameBuffer> {
+        read_netpbm(input).map(NetpbmImage::into_rgba_frame_buffer)
+    }
+
+    /// Makes every pixel completely transparent again
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|sample| *sample = 0);
+    }
+
+    /// Flattens the buffer onto the given background color, producing a
+    /// FrameBuffer that looks the same as this one would over that background
+    pub fn to_frame_buffer(&self, background: Color) -> FrameBuffer {
+        let mut pixels = Vec::with_capacity(self.pixels.len() / 4 * 3);
+        for pixel in self.pixels.chunks(4) {
+            let clear = 255 - pixel[3];
+            pixels.extend_from_slice(&[
+                pixel[0].saturating_add(mul(background.r, clear)),
+                pixel[1].saturating_add(mul(background.g, clear)),
+                pixel[2].saturating_add(mul(background.b, clear)),
+            ]);
+        }
+
+        FrameBuffer {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Copies the alpha value of every pixel into a stencil
+    pub fn alpha(&self) -> StencilBuffer {
+        StencilBuffer {
+            pixels: self.pixels.chunks(4).map(|pixel| pixel[3]).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Dumps the buffer as an RGBA PNG image, with each (keyword, text) pair
+    /// stored as a tEXt chunk. The text has to be Latin-1, and keywords that
+    /// PNG doesn't allow are rejected.
+    pub fn write_png(&self, output: &mut impl io::Write, text: &[(&str, &str)]) -> io::Result<()> {
+        png::write_png(
+            output,
+            self.width,
+            self.height,
+            png::PngColor::Rgba,
+            &self.straight_pixels(),
+            text,
+        )
+    }
+
+    /// Dumps the buffer as a PAM image with the RGB_ALPHA tuple type
+    pub fn write_pam(&self, output: &mut impl io::Write) -> io::Result<()> {
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height
+        );
+        write_all(output, header.as_bytes())?;
+        write_all(output, &self.straight_pixels())
+    }
+
+    /// Creates a copy of the buffer stretched or shrunk to the given size
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> RgbaFrameBuffer {
+        let region = (0, 0, self.width as i64, self.height as i64);
+        RgbaFrameBuffer::resampled(self, region, width, height, filter)
+    }
+
+    /// Returns the pixels with the alpha divided back out of the colors, which
+    /// is what image formats expect
+    fn straight_pixels(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for (i, pixel) in self.pixels.chunks(4).enumerate() {
+            let color = self.unpremultiply(i * 4);
+            pixels.extend_from_slice(&[color.r, color.g, color.b, pixel[3]]);
+        }
+
+        pixels
+    }
+
+    /// Reads the pixel starting at the given offset as a straight color
+    fn unpremultiply(&self, offset: usize) -> Color {
+        let alpha = self.pixels[offset + 3];
+        if alpha == 0 {
+            return Color::rgba(0, 0, 0, 0);
+        }
+
+        let channel =
+            |value: u8| ((value as u16 * 255 + alpha as u16 / 2) / alpha as u16).min(255) as u8;
+        Color::rgba(
+            channel(self.pixels[offset]),
+            channel(self.pixels[offset + 1]),
+            channel(self.pixels[offset + 2]),
+            alpha,
+        )
+    }
+
+    /// Gets the offset of the given pixel, if it's inside of the buffer
+    fn offset(&self, x: i64, y: i64) -> Option<usize> {
+        if x < 0 || x >= self.width as i64 || y < 0 || y >= self.height as i64 {
+            None
+        } else {
+            Some(((y * (self.width as i64) + x) * 4) as usize)
+        }
+    }
+}
+
+impl GraphicBuffer<Color> for RgbaFrameBuffer {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn get_point(&self, x: i64, y: i64) -> Option<Color> {
+        self.offset(x, y).map(|offset| self.unpremultiply(offset))
+    }
+
+    fn put_point(&mut self, x: i64, y: i64, color: Color) {
+        let offset = match self.offset(x, y) {
+            Some(offset) if color.alpha != 0 => offset,
+            _ => return,
+        };
+
+        /*
+        With premultiplied colors, drawing over a pixel is the same sum for
+        every channel, alpha included:
+
+        result = source + destination * (1 - source alpha)
+         */
+        let clear = 255 - color.alpha;
+        let source = [
+            mul(color.r, color.alpha),
+            mul(color.g, color.alpha),
+            mul(color.b, color.alpha),
+            color.alpha,
+        ];
+
+        for (channel, source) in source.iter().enumerate() {
+            let destination = &mut self.pixels[offset + channel];
+            *destination = source.saturating_add(mul(*destination, clear));
+        }
+    }
+
+    fn blend_point(&mut self, x: i64, y: i64, color: Color, mode: BlendMode) {
+        if mode == BlendMode::Normal {
+            self.put_point(x, y, color);
+            return;
+        }
+
+        let offset = match self.offset(x, y) {
+            Some(offset) if color.alpha != 0 => offset,
+            _ => return,
+        };
+
+        /*
+        Where only one of the colors is present, it shows through as it would
+        with Normal. The blend mode only decides the color of the part where
+        both of them overlap, which covers source alpha * destination alpha of
+        the pixel:
+
+        result = source * (1 - destination alpha)
+               + destination * (1 - source alpha)
+               + blend(destination, source) * source alpha * destination alpha
+
+        Here source and destination are premultiplied, while the blend works
+        on straight colors.
+         */
+        let base = self.unpremultiply(offset);
+        let (source_alpha, base_alpha) = (color.alpha as f64 / 255.0, base.alpha as f64 / 255.0);
+        let channels = [(color.r, base.r), (color.g, base.g), (color.b, base.b)];
+
+        for (channel, &(source, straight)) in channels.iter().enumerate() {
+            let source_part = source as f64 * source_alpha * (1.0 - base_alpha);
+            let base_part = self.pixels[offset + channel] as f64 * (1.0 - source_alpha);
+            let both_part = mode.apply(straight, source) as f64 * source_alpha * base_alpha;
+            self.pixels[offset + channel] =
+                (source_part + base_part + both_part).round().min(255.0) as u8;
+        }
+
+        let alpha = source_alpha + base_alpha - source_alpha * base_alpha;
+        self.pixels[offset + 3] = (alpha * 255.0).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::decode_png;
+
+    /// Gets all four channels of the color
+    fn channels(color: Color) -> [u8; 4] {
+        [color.r, color.g, color.b, color.alpha]
+    }
+
+    /// Builds a 3x1 buffer with an opaque, a translucent and a transparent
+    /// pixel
+    fn mixed_buffer() -> RgbaFrameBuffer {
+        let mut buffer = RgbaFrameBuffer::new(3, 1);
+        buffer.put_point(0, 0, Color::rgba(200, 100, 30, 255));
+        buffer.put_point(1, 0, Color::rgba(255, 128, 0, 51));
+        buffer
+    }
+
+    /// The pixels of mixed_buffer as straight RGBA. The translucent green
+    /// isn't quite 128 since it only had 26 levels to be stored in.
+    const MIXED_STRAIGHT: [u8; 12] = [200, 100, 30, 255, 255, 130, 0, 51, 0, 0, 0, 0];
+
+    #[test]
+    fn premultiplied_round_trip() {
+        /*
+        Storing a channel premultiplied rounds it to one of alpha + 1
+        levels, so reading it back can be off by up to half of a level, plus
+        the rounding on the way back. Reading a color and writing it back has
+        to give exactly the same samples though.
+         */
+        for alpha in 1..=255u8 {
+            for value in 0..=255u8 {
+                let mut buffer = RgbaFrameBuffer::new(1, 1);
+                buffer.put_point(0, 0, Color::rgba(value, 255 - value, value / 2, alpha));
+                let color = buffer.get_point(0, 0).unwrap();
+                assert_eq!(color.alpha, alpha);
+
+                let error = (color.r as f64 - value as f64).abs();
+                assert!(
+                    error <= 127.5 / alpha as f64 + 0.5,
+                    "{} at {}",
+                    value,
+                    alpha
+                );
+
+                let samples = buffer.pixels.clone();
+                let mut copy = RgbaFrameBuffer::new(1, 1);
+                copy.put_point(0, 0, color);
+                assert_eq!(copy.pixels, samples, "{} at {}", value, alpha);
+            }
+        }
+
+        let mut buffer = RgbaFrameBuffer::new(1, 1);
+        buffer.put_point(0, 0, Color::rgb(1, 128, 254));
+        assert_eq!(buffer.pixels, [1, 128, 254, 255]);
+    }
+
+    #[test]
+    fn translucent_over_opaque() {
+        let mut buffer = RgbaFrameBuffer::new(1, 1);
+        buffer.put_point(0, 0, Color::rgb(200, 100, 30));
+        buffer.put_point(0, 0, Color::rgba(40, 120, 240, 128));
+        assert_eq!(buffer.pixels, [120, 110, 135, 255]);
+
+        // Flattening an opaque buffer doesn't let the background through
+        let flat = buffer.to_frame_buffer(Color::rgb(255, 0, 255));
+        assert_eq!(flat.pixels, [120, 110, 135]);
+    }
+
+    #[test]
+    fn translucent_over_translucent() {
+        let mut buffer = RgbaFrameBuffer::new(1, 1);
+        buffer.put_point(0, 0, Color::rgba(255, 0, 0, 128));
+        assert_eq!(buffer.pixels, [128, 0, 0, 128]);
+
+        // 0.5 + 0.5 * (1 - 0.5) of the pixel is covered
+        buffer.put_point(0, 0, Color::rgba(0, 0, 255, 128));
+        assert_eq!(buffer.pixels, [64, 0, 128, 192]);
+
+        let flat = buffer.to_frame_buffer(Color::rgb(0, 255, 0));
+        assert_eq!(flat.pixels, [64, 63, 128]);
+    }
+
+    #[test]
+    fn zero_alpha() {
+        let mut buffer = RgbaFrameBuffer::new(2, 1);
+        assert_eq!(channels(buffer.get_point(0, 0).unwrap()), [0, 0, 0, 0]);
+
+        // Drawing nothing over a transparent or an opaque pixel changes
+        // nothing, whatever the mode
+        buffer.put_point(1, 0, Color::rgb(10, 20, 30));
+        for &mode in [BlendMode::Normal, BlendMode::Screen, BlendMode::Difference].iter() {
+            buffer.blend_point(0, 0, Color::rgba(255, 255, 255, 0), mode);
+            buffer.blend_point(1, 0, Color::rgba(255, 255, 255, 0), mode);
+        }
+        assert_eq!(buffer.pixels, [0, 0, 0, 0, 10, 20, 30, 255]);
+
+        // A blend mode has nothing to mix with over a transparent pixel, so
+        // the drawn color shows as it is
+        buffer.blend_point(0, 0, Color::rgba(200, 100, 50, 255), BlendMode::Multiply);
+        assert_eq!(buffer.pixels[..4], [200, 100, 50, 255]);
+
+        buffer.clear();
+        assert!(buffer.pixels.iter().all(|&sample| sample == 0));
+    }
+
+    #[test]
+    fn blend_mode_over_opaque() {
+        // Over an opaque pixel a blend mode gives the same as FrameBuffer
+        let mut buffer = RgbaFrameBuffer::new(1, 1);
+        buffer.put_point(0, 0, Color::rgb(200, 100, 30));
+        buffer.blend_point(0, 0, Color::rgba(40, 120, 240, 255), BlendMode::Multiply);
+        assert_eq!(buffer.pixels, [31, 47, 28, 255]);
+    }
+
+    #[test]
+    fn pam_is_straight() {
+        let mut output = Vec::new();
+        mixed_buffer().write_pam(&mut output).unwrap();
+
+        let header = b"P7\nWIDTH 3\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n";
+        assert_eq!(output[..header.len()], header[..]);
+        assert_eq!(output[header.len()..], MIXED_STRAIGHT);
+    }
+
+    #[test]
+    fn png_is_straight() {
+        let mut output = Vec::new();
+        mixed_buffer().write_png(&mut output, &[]).unwrap();
+        assert_eq!(decode_png(&output), (3, 1, 6, MIXED_STRAIGHT.to_vec()));
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{Chroma, FrameBuffer, GifPalette, GifWriter, Y4mWriter};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A destination for the frames produced by a demo
+pub trait FrameSink {
+    /// Outputs a single frame
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()>;
+
+    /// Ends the output after the last frame, writing anything that's still
+    /// buffered along with any trailer the format needs. Some formats (like
+    /// GIF) aren't valid until this is done.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes frames as a stream of binary PPM images, which can be piped into
+/// mpv or an encoder that understands NetPBM streams
+pub struct PpmSink<W: io::Write> {
+    output: W,
+}
+
+impl<W: io::Write> PpmSink<W> {
+    /// Creates a sink that writes each frame to the given stream
+    pub fn new(output: W) -> PpmSink<W> {
+        PpmSink { output }
+    }
+}
+
+impl<W: io::Write> FrameSink for PpmSink<W> {
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        frame.write(&mut self.output)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl<W: io::Write> FrameSink for Y4mWriter<W> {
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        Y4mWriter::write_frame(self, frame)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Y4mWriter::flush(self)
+    }
+}
+
+/// The file formats that image files can be written in
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Pam,
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks the format based upon the extension of the path
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "pam" => Some(ImageFormat::Pam),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    fn write(self, frame: &FrameBuffer, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        match self {
+            ImageFormat::Ppm => frame.write(&mut file),
+            ImageFormat::Pam => frame.write_pam(&mut file),
+            ImageFormat::Png => frame.write_png(&mut file, &[]),
+        }
+    }
+}
+
+/// Writes each frame to the same PNG file, so that the file always holds the
+/// most recent frame
+pub struct PngSink {
+    path: PathBuf,
+}
+
+impl PngSink {
+    /// Creates a sink that writes to the given path
+    pub fn new(path: impl Into<PathBuf>) -> PngSink {
+        PngSink { path: path.into() }
+    }
+}
+
+impl FrameSink for PngSink {
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        ImageFormat::Png.write(frame, &self.path)
+    }
+}
+
+/// Writes each frame to its own numbered image file
+pub struct ImageSequence {
+    prefix: String,
+    suffix: String,
+    digits: usize,
+    format: ImageFormat,
+    index: u64,
+}
+
+impl ImageSequence {
+    /// Creates a sink from a file name pattern, where the run of '#'
+    /// characters is replaced by the zero-padded frame number (for example,
+    /// "frames/####.png"). The extension of the pattern determines the format.
+    pub fn new(pattern: &str) -> io::Result<ImageSequence> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        let start = pattern
+            .find('#')
+            .ok_or_else(|| invalid("image sequence pattern must contain '#'"))?;
+        let digits = pattern[start..].chars().take_while(|&c| c == '#').count();
+
+        let format = ImageFormat::from_path(Path::new(pattern))
+            .ok_or_else(|| invalid("image sequence must end in .ppm, .pam or .png"))?;
+
+        Ok(ImageSequence {
+            prefix: pattern[..start].to_string(),
+            suffix: pattern[start + digits..].to_string(),
+            digits,
+            format,
+            index: 0,
+        })
+    }
+}
+
+impl FrameSink for ImageSequence {
+    fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        let path = format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            self.index,
+            self.suffix,
+            width = self.digits
+        );
+        self.format.write(frame, Path::new(&path))?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+/// Creates a sink from a textual description, which is one of:
+///
+/// - `ppm`: a stream of PPM images on stdout
+/// - `y4m[:FPS[:CHROMA]]`: a YUV4MPEG2 stream on stdout, at 30 FPS by default
+///   with either 420 (the default) or 444 chroma. The FPS is either a whole
+///   number or a fraction like `30000:1001`.
+/// - `png:PATH`: a single PNG file that is rewritten for every frame
+/// - `seq:PATTERN`: numbered image files, see `ImageSequence::new`
+/// - `gif:PATH`: an animated GIF at roughly 30 FPS, with a palette per frame
+pub fn open_sink(spec: &str) -> io::Result<Box<dyn FrameSink>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let (kind, argument) = match spec.find(':') {
+        Some(index) => (&spec[..index], Some(&spec[index + 1..])),
+        None => (spec, None),
+    };
+
+    match (kind, argument) {
+        ("ppm", None) => Ok(Box::new(PpmSink::new(io::stdout()))),
+        ("y4m", _) => {
+            let (fps, chroma) = y4m_options(argument.unwrap_or(""))?;
+            Ok(Box::new(Y4mWriter::new(io::stdout(), fps.0, fps.1, chroma)))
+        }
+        ("png", Some(path)) => Ok(Box::new(PngSink::new(path))),
+        ("seq", Some(pattern)) => Ok(Box::new(ImageSequence::new(pattern)?)),
+        ("gif", Some(path)) => {
+            let file = io::BufWriter::new(File::create(path)?);
+            Ok(Box::new(GifWriter::new(file, 3, GifPalette::PerFrame)?))
+        }
+        _ => Err(invalid(format!("unknown output: {}", spec))),
+    }
+}
+
+/// Reads the frame rate and chroma from the options of a `y4m` sink, which
+/// are the FPS as either `NUM` or `NUM:DEN`, optionally followed by the chroma
+fn y4m_options(argument: &str) -> io::Result<((u32, u32), Chroma)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let rate = |text: &str| match text.parse() {
+        Ok(0) | Err(_) => Err(invalid(format!("invalid Y4M frame rate: {}", argument))),
+        Ok(rate) => Ok(rate),
+    };
+
+    /*
+    The first option is always the start of the frame rate. After that, 420
+    and 444 are taken as the chroma rather than as the denominator, since a
+    rate that far under 1 FPS isn't something anyone would want.
+     */
+    let options: Vec<&str> = argument.split(':').collect();
+    let (numerator, denominator, chroma) = match options[..] {
+        [numerator] => (numerator, None, None),
+        [numerator, chroma @ ("420" | "444")] => (numerator, None, Some(chroma)),
+        [numerator, denominator] => (numerator, Some(denominator), None),
+        [numerator, denominator, chroma] => (numerator, Some(denominator), Some(chroma)),
+        _ => return Err(invalid(format!("invalid Y4M options: {}", argument))),
+    };
+
+    let numerator = match numerator {
+        "" => 30,
+        numerator => rate(numerator)?,
+    };
+    let denominator = match denominator {
+        None => 1,
+        Some(denominator) => rate(denominator)?,
+    };
+
+    let chroma = match chroma {
+        None | Some("420") => Chroma::C420,
+        Some("444") => Chroma::C444,
+        Some(chroma) => return Err(invalid(format!("invalid Y4M chroma: {}", chroma))),
+    };
+
+    Ok(((numerator, denominator), chroma))
+}
+
+/// Creates a sink from the first command-line argument, falling back to a PPM
+/// stream on stdout if there isn't one
+pub fn sink_from_args() -> io::Result<Box<dyn FrameSink>> {
+    match std::env::args().nth(1) {
+        Some(spec) => open_sink(&spec),
+        None => open_sink("ppm"),
+    }
+}
+
+/// Reads the number of frames to write from the second command-line argument,
+/// if there is one. Demos that would otherwise run until they're interrupted
+/// use this to stop on their own, so that they can finish their sink.
+pub fn frame_limit_from_args() -> io::Result<Option<u64>> {
+    match std::env::args().nth(2) {
+        Some(frames) => frames.parse().map(Some).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid frame count: {}", frames),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn y4m_frame_rates() {
+        let cases = [
+            ("", (30, 1), Chroma::C420),
+            ("24", (24, 1), Chroma::C420),
+            ("30000:1001", (30000, 1001), Chroma::C420),
+            ("30:444", (30, 1), Chroma::C444),
+            ("30:420", (30, 1), Chroma::C420),
+            (":444", (30, 1), Chroma::C444),
+            ("60000:1001:444", (60000, 1001), Chroma::C444),
+            ("25:2:420", (25, 2), Chroma::C420),
+        ];
+
+        for &(argument, fps, chroma) in cases.iter() {
+            let options = y4m_options(argument).unwrap();
+            assert_eq!(options.0, fps, "{}", argument);
+            assert!(options.1 == chroma, "{}", argument);
+        }
+    }
+
+    #[test]
+    fn y4m_bad_options() {
+        for &argument in [
+            "0",
+            "0:1",
+            "30:0",
+            "30000:0:444",
+            "-5",
+            "ten",
+            "30:1:422",
+            "30:1:444:1",
+            "1.5",
+        ]
+        .iter()
+        {
+            let error = y4m_options(argument).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", argument);
+        }
+
+        assert!(open_sink("y4m:0").is_err());
+    }
+}
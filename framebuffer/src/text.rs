@@ -0,0 +1,417 @@
+use crate::{Canvas, GraphicBuffer};
+
+/// The size of each character in the font, in pixels at a scale of 1
+const GLYPH_WIDTH: i64 = 6;
+const GLYPH_HEIGHT: i64 = 10;
+
+/// How far the baseline is below the top of each character
+const GLYPH_ASCENT: i64 = 8;
+
+/// Decides which part of each line of text lines up with the x coordinate
+/// given to draw_text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Decides which part of the text lines up with the y coordinate given to
+/// draw_text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextBaseline {
+    /// The top of the first line
+    Top,
+    /// Halfway between the top of the first line and the bottom of the last
+    Middle,
+    /// The line that the first line's letters sit on, which descenders like
+    /// the tail of a g hang below
+    Alphabetic,
+    /// The bottom of the last line, below any descenders
+    Bottom,
+}
+
+/// Gets the rows of the character's glyph, with the leftmost pixel in the
+/// highest bit. Characters outside of printable ASCII are drawn as '?'.
+fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT as usize] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &GLYPHS[index]
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Sets how many pixels wide and tall each pixel of the font is drawn
+    pub fn set_text_scale(&mut self, scale: i64) {
+        self.text_scale = scale.max(1);
+    }
+
+    /// Sets how lines of text line up horizontally with the point they're
+    /// drawn at
+    pub fn set_text_align(&mut self, align: TextAlign) {
+        self.text_align = align;
+    }
+
+    /// Sets how text lines up vertically with the point it's drawn at
+    pub fn set_text_baseline(&mut self, baseline: TextBaseline) {
+        self.text_baseline = baseline;
+    }
+
+    /// Returns the width and height of the area that the text covers at the
+    /// current scale. Each line break starts a new line.
+    pub fn measure_text(&self, text: &str) -> (i64, i64) {
+        let lines = text.split('\n');
+        let columns = lines.clone().map(|line| line.chars().count()).max();
+        let rows = lines.count() as i64;
+
+        let scale = self.text_scale;
+        (
+            columns.unwrap_or(0) as i64 * GLYPH_WIDTH * scale,
+            rows * GLYPH_HEIGHT * scale,
+        )
+    }
+
+    /// Draws the text in the current fill color using the built-in 6x10 pixel
+    /// font, lined up with the given point according to the current text
+    /// alignment and baseline. Each line break starts a new line.
+    pub fn draw_text(&mut self, x: i64, y: i64, text: &str) {
+        let scale = self.text_scale;
+        let (width, height) = (GLYPH_WIDTH * scale, GLYPH_HEIGHT * scale);
+        let (_, total_height) = self.measure_text(text);
+
+        let top = match self.text_baseline {
+            TextBaseline::Top => y,
+            TextBaseline::Middle => y - total_height / 2,
+            TextBaseline::Alphabetic => y - GLYPH_ASCENT * scale,
+            TextBaseline::Bottom => y - total_height,
+        };
+
+        for (row, line) in text.split('\n').enumerate() {
+            let line_width = line.chars().count() as i64 * width;
+            let left = match self.text_align {
+                TextAlign::Left => x,
+                TextAlign::Center => x - line_width / 2,
+                TextAlign::Right => x - line_width,
+            };
+
+            let line_top = top + row as i64 * height;
+            for (column, c) in line.chars().enumerate() {
+                let glyph_left = left + column as i64 * width;
+                for (py, bits) in glyph(c).iter().enumerate() {
+                    for px in 0..GLYPH_WIDTH {
+                        if bits & (0x80 >> px) != 0 {
+                            self.fill_rect(
+                                glyph_left + px * scale,
+                                line_top + py as i64 * scale,
+                                scale,
+                                scale,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/*
+The glyphs for ' ' through '~' from the public domain 6x10 "fixed" font that
+comes with X11. Each glyph is 10 rows of 6 pixels, with the leftmost pixel in
+the highest bit of each row.
+ */
+const GLYPHS: [[u8; GLYPH_HEIGHT as usize]; 95] = [
+    // ' '
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '!'
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x20, 0x00, 0x00],
+    // '"'
+    [0x00, 0x50, 0x50, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '#'
+    [0x00, 0x50, 0x50, 0xf8, 0x50, 0xf8, 0x50, 0x50, 0x00, 0x00],
+    // '$'
+    [0x00, 0x20, 0x70, 0xa0, 0x70, 0x28, 0x70, 0x20, 0x00, 0x00],
+    // '%'
+    [0x00, 0x48, 0xa8, 0x50, 0x20, 0x50, 0xa8, 0x90, 0x00, 0x00],
+    // '&'
+    [0x00, 0x40, 0xa0, 0xa0, 0x40, 0xa8, 0x90, 0x68, 0x00, 0x00],
+    // "'"
+    [0x00, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '('
+    [0x00, 0x10, 0x20, 0x40, 0x40, 0x40, 0x20, 0x10, 0x00, 0x00],
+    // ')'
+    [0x00, 0x40, 0x20, 0x10, 0x10, 0x10, 0x20, 0x40, 0x00, 0x00],
+    // '*'
+    [0x00, 0x00, 0x88, 0x50, 0xf8, 0x50, 0x88, 0x00, 0x00, 0x00],
+    // '+'
+    [0x00, 0x00, 0x20, 0x20, 0xf8, 0x20, 0x20, 0x00, 0x00, 0x00],
+    // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x20, 0x40, 0x00],
+    // '-'
+    [0x00, 0x00, 0x00, 0x00, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '.'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x70, 0x20, 0x00],
+    // '/'
+    [0x00, 0x08, 0x08, 0x10, 0x20, 0x40, 0x80, 0x80, 0x00, 0x00],
+    // '0'
+    [0x00, 0x20, 0x50, 0x88, 0x88, 0x88, 0x50, 0x20, 0x00, 0x00],
+    // '1'
+    [0x00, 0x20, 0x60, 0xa0, 0x20, 0x20, 0x20, 0xf8, 0x00, 0x00],
+    // '2'
+    [0x00, 0x70, 0x88, 0x08, 0x30, 0x40, 0x80, 0xf8, 0x00, 0x00],
+    // '3'
+    [0x00, 0xf8, 0x08, 0x10, 0x30, 0x08, 0x88, 0x70, 0x00, 0x00],
+    // '4'
+    [0x00, 0x10, 0x30, 0x50, 0x90, 0xf8, 0x10, 0x10, 0x00, 0x00],
+    // '5'
+    [0x00, 0xf8, 0x80, 0xb0, 0xc8, 0x08, 0x88, 0x70, 0x00, 0x00],
+    // '6'
+    [0x00, 0x30, 0x40, 0x80, 0xb0, 0xc8, 0x88, 0x70, 0x00, 0x00],
+    // '7'
+    [0x00, 0xf8, 0x08, 0x10, 0x10, 0x20, 0x40, 0x40, 0x00, 0x00],
+    // '8'
+    [0x00, 0x70, 0x88, 0x88, 0x70, 0x88, 0x88, 0x70, 0x00, 0x00],
+    // '9'
+    [0x00, 0x70, 0x88, 0x98, 0x68, 0x08, 0x10, 0x60, 0x00, 0x00],
+    // ':'
+    [0x00, 0x00, 0x20, 0x70, 0x20, 0x00, 0x20, 0x70, 0x20, 0x00],
+    // ';'
+    [0x00, 0x00, 0x20, 0x70, 0x20, 0x00, 0x30, 0x20, 0x40, 0x00],
+    // '<'
+    [0x00, 0x08, 0x10, 0x20, 0x40, 0x20, 0x10, 0x08, 0x00, 0x00],
+    // '='
+    [0x00, 0x00, 0x00, 0xf8, 0x00, 0xf8, 0x00, 0x00, 0x00, 0x00],
+    // '>'
+    [0x00, 0x40, 0x20, 0x10, 0x08, 0x10, 0x20, 0x40, 0x00, 0x00],
+    // '?'
+    [0x00, 0x70, 0x88, 0x10, 0x20, 0x20, 0x00, 0x20, 0x00, 0x00],
+    // '@'
+    [0x00, 0x70, 0x88, 0x98, 0xa8, 0xb0, 0x80, 0x70, 0x00, 0x00],
+    // 'A'
+    [0x00, 0x20, 0x50, 0x88, 0x88, 0xf8, 0x88, 0x88, 0x00, 0x00],
+    // 'B'
+    [0x00, 0xf0, 0x48, 0x48, 0x70, 0x48, 0x48, 0xf0, 0x00, 0x00],
+    // 'C'
+    [0x00, 0x70, 0x88, 0x80, 0x80, 0x80, 0x88, 0x70, 0x00, 0x00],
+    // 'D'
+    [0x00, 0xf0, 0x48, 0x48, 0x48, 0x48, 0x48, 0xf0, 0x00, 0x00],
+    // 'E'
+    [0x00, 0xf8, 0x80, 0x80, 0xf0, 0x80, 0x80, 0xf8, 0x00, 0x00],
+    // 'F'
+    [0x00, 0xf8, 0x80, 0x80, 0xf0, 0x80, 0x80, 0x80, 0x00, 0x00],
+    // 'G'
+    [0x00, 0x70, 0x88, 0x80, 0x80, 0x98, 0x88, 0x70, 0x00, 0x00],
+    // 'H'
+    [0x00, 0x88, 0x88, 0x88, 0xf8, 0x88, 0x88, 0x88, 0x00, 0x00],
+    // 'I'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00, 0x00],
+    // 'J'
+    [0x00, 0x38, 0x10, 0x10, 0x10, 0x10, 0x90, 0x60, 0x00, 0x00],
+    // 'K'
+    [0x00, 0x88, 0x90, 0xa0, 0xc0, 0xa0, 0x90, 0x88, 0x00, 0x00],
+    // 'L'
+    [0x00, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xf8, 0x00, 0x00],
+    // 'M'
+    [0x00, 0x88, 0x88, 0xd8, 0xa8, 0x88, 0x88, 0x88, 0x00, 0x00],
+    // 'N'
+    [0x00, 0x88, 0x88, 0xc8, 0xa8, 0x98, 0x88, 0x88, 0x00, 0x00],
+    // 'O'
+    [0x00, 0x70, 0x88, 0x88, 0x88, 0x88, 0x88, 0x70, 0x00, 0x00],
+    // 'P'
+    [0x00, 0xf0, 0x88, 0x88, 0xf0, 0x80, 0x80, 0x80, 0x00, 0x00],
+    // 'Q'
+    [0x00, 0x70, 0x88, 0x88, 0x88, 0x88, 0xa8, 0x70, 0x08, 0x00],
+    // 'R'
+    [0x00, 0xf0, 0x88, 0x88, 0xf0, 0xa0, 0x90, 0x88, 0x00, 0x00],
+    // 'S'
+    [0x00, 0x70, 0x88, 0x80, 0x70, 0x08, 0x88, 0x70, 0x00, 0x00],
+    // 'T'
+    [0x00, 0xf8, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00],
+    // 'U'
+    [0x00, 0x88, 0x88, 0x88, 0x88, 0x88, 0x88, 0x70, 0x00, 0x00],
+    // 'V'
+    [0x00, 0x88, 0x88, 0x88, 0x50, 0x50, 0x50, 0x20, 0x00, 0x00],
+    // 'W'
+    [0x00, 0x88, 0x88, 0x88, 0xa8, 0xa8, 0xd8, 0x88, 0x00, 0x00],
+    // 'X'
+    [0x00, 0x88, 0x88, 0x50, 0x20, 0x50, 0x88, 0x88, 0x00, 0x00],
+    // 'Y'
+    [0x00, 0x88, 0x88, 0x50, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00],
+    // 'Z'
+    [0x00, 0xf8, 0x08, 0x10, 0x20, 0x40, 0x80, 0xf8, 0x00, 0x00],
+    // '['
+    [0x00, 0x70, 0x40, 0x40, 0x40, 0x40, 0x40, 0x70, 0x00, 0x00],
+    // '\\'
+    [0x00, 0x80, 0x80, 0x40, 0x20, 0x10, 0x08, 0x08, 0x00, 0x00],
+    // ']'
+    [0x00, 0x70, 0x10, 0x10, 0x10, 0x10, 0x10, 0x70, 0x00, 0x00],
+    // '^'
+    [0x00, 0x20, 0x50, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '_'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x00],
+    // '`'
+    [0x20, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 'a'
+    [0x00, 0x00, 0x00, 0x70, 0x08, 0x78, 0x88, 0x78, 0x00, 0x00],
+    // 'b'
+    [0x00, 0x80, 0x80, 0xb0, 0xc8, 0x88, 0xc8, 0xb0, 0x00, 0x00],
+    // 'c'
+    [0x00, 0x00, 0x00, 0x70, 0x88, 0x80, 0x88, 0x70, 0x00, 0x00],
+    // 'd'
+    [0x00, 0x08, 0x08, 0x68, 0x98, 0x88, 0x98, 0x68, 0x00, 0x00],
+    // 'e'
+    [0x00, 0x00, 0x00, 0x70, 0x88, 0xf8, 0x80, 0x70, 0x00, 0x00],
+    // 'f'
+    [0x00, 0x30, 0x48, 0x40, 0xf0, 0x40, 0x40, 0x40, 0x00, 0x00],
+    // 'g'
+    [0x00, 0x00, 0x00, 0x78, 0x88, 0x88, 0x78, 0x08, 0x88, 0x70],
+    // 'h'
+    [0x00, 0x80, 0x80, 0xb0, 0xc8, 0x88, 0x88, 0x88, 0x00, 0x00],
+    // 'i'
+    [0x00, 0x20, 0x00, 0x60, 0x20, 0x20, 0x20, 0x70, 0x00, 0x00],
+    // 'j'
+    [0x00, 0x08, 0x00, 0x18, 0x08, 0x08, 0x08, 0x48, 0x48, 0x30],
+    // 'k'
+    [0x00, 0x80, 0x80, 0x88, 0x90, 0xe0, 0x90, 0x88, 0x00, 0x00],
+    // 'l'
+    [0x00, 0x60, 0x20, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00, 0x00],
+    // 'm'
+    [0x00, 0x00, 0x00, 0xd0, 0xa8, 0xa8, 0xa8, 0x88, 0x00, 0x00],
+    // 'n'
+    [0x00, 0x00, 0x00, 0xb0, 0xc8, 0x88, 0x88, 0x88, 0x00, 0x00],
+    // 'o'
+    [0x00, 0x00, 0x00, 0x70, 0x88, 0x88, 0x88, 0x70, 0x00, 0x00],
+    // 'p'
+    [0x00, 0x00, 0x00, 0xb0, 0xc8, 0x88, 0xc8, 0xb0, 0x80, 0x80],
+    // 'q'
+    [0x00, 0x00, 0x00, 0x68, 0x98, 0x88, 0x98, 0x68, 0x08, 0x08],
+    // 'r'
+    [0x00, 0x00, 0x00, 0xb0, 0xc8, 0x80, 0x80, 0x80, 0x00, 0x00],
+    // 's'
+    [0x00, 0x00, 0x00, 0x70, 0x80, 0x70, 0x08, 0xf0, 0x00, 0x00],
+    // 't'
+    [0x00, 0x40, 0x40, 0xf0, 0x40, 0x40, 0x48, 0x30, 0x00, 0x00],
+    // 'u'
+    [0x00, 0x00, 0x00, 0x88, 0x88, 0x88, 0x98, 0x68, 0x00, 0x00],
+    // 'v'
+    [0x00, 0x00, 0x00, 0x88, 0x88, 0x50, 0x50, 0x20, 0x00, 0x00],
+    // 'w'
+    [0x00, 0x00, 0x00, 0x88, 0x88, 0xa8, 0xa8, 0x50, 0x00, 0x00],
+    // 'x'
+    [0x00, 0x00, 0x00, 0x88, 0x50, 0x20, 0x50, 0x88, 0x00, 0x00],
+    // 'y'
+    [0x00, 0x00, 0x00, 0x88, 0x88, 0x98, 0x68, 0x08, 0x88, 0x70],
+    // 'z'
+    [0x00, 0x00, 0x00, 0xf8, 0x10, 0x20, 0x40, 0xf8, 0x00, 0x00],
+    // '{'
+    [0x00, 0x18, 0x20, 0x10, 0x60, 0x10, 0x20, 0x18, 0x00, 0x00],
+    // '|'
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00],
+    // '}'
+    [0x00, 0x60, 0x10, 0x20, 0x18, 0x20, 0x10, 0x60, 0x00, 0x00],
+    // '~'
+    [0x00, 0x48, 0xa8, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StencilBuffer;
+
+    /// Creates an empty canvas that marks the pixels it draws with 1
+    fn new_canvas() -> Canvas<u8, StencilBuffer> {
+        Canvas::new(StencilBuffer::new(100, 60), 1, 1)
+    }
+
+    /// Finds the leftmost pixel drawn on the rows from top up to but not
+    /// including bottom
+    fn left_edge(canvas: &Canvas<u8, StencilBuffer>, top: i64, bottom: i64) -> Option<i64> {
+        (0..canvas.width() as i64)
+            .find(|&x| (top..bottom).any(|y| canvas.get_point(x, y) == Some(1)))
+    }
+
+    /// Finds how far in from the left of its cell the character's glyph
+    /// starts, in font pixels
+    fn glyph_inset(c: char) -> i64 {
+        glyph(c)
+            .iter()
+            .map(|bits| bits.leading_zeros())
+            .min()
+            .unwrap() as i64
+    }
+
+    #[test]
+    fn measure_lines() {
+        let mut canvas = new_canvas();
+        assert_eq!(canvas.measure_text("hello"), (30, 10));
+        assert_eq!(canvas.measure_text("ab\ncdef\nghi"), (24, 30));
+        assert_eq!(canvas.measure_text("ab\n"), (12, 20));
+        assert_eq!(canvas.measure_text(""), (0, 10));
+
+        // Characters are counted rather than bytes
+        assert_eq!(canvas.measure_text("é\nÅß"), (12, 20));
+
+        canvas.set_text_scale(3);
+        assert_eq!(canvas.measure_text("ab\ncdef\nghi"), (72, 90));
+
+        canvas.set_text_scale(0);
+        assert_eq!(canvas.measure_text("ab"), (12, 10));
+    }
+
+    #[test]
+    fn alignment_left_edges() {
+        // Each line is lined up on its own, so the short line moves in
+        let inset = glyph_inset('M') * 2;
+        let expected = [
+            (TextAlign::Left, 50 + inset, 50 + inset),
+            (TextAlign::Center, 50 - 18 + inset, 50 - 6 + inset),
+            (TextAlign::Right, 50 - 36 + inset, 50 - 12 + inset),
+        ];
+
+        for &(align, first, second) in expected.iter() {
+            let mut canvas = new_canvas();
+            canvas.set_text_scale(2);
+            canvas.set_text_align(align);
+            canvas.draw_text(50, 5, "MMM\nM");
+            assert_eq!(left_edge(&canvas, 5, 25), Some(first), "{:?}", align);
+            assert_eq!(left_edge(&canvas, 25, 45), Some(second), "{:?}", align);
+            assert_eq!(left_edge(&canvas, 45, 60), None, "{:?}", align);
+        }
+    }
+
+    #[test]
+    fn baselines() {
+        let top_row = |canvas: &Canvas<u8, StencilBuffer>| {
+            (0..60).find(|&y| (0..100).any(|x| canvas.get_point(x, y) == Some(1)))
+        };
+        let inset = glyph('M').iter().position(|&bits| bits != 0).unwrap() as i64;
+        let expected = [
+            (TextBaseline::Top, 30),
+            (TextBaseline::Middle, 30 - 10),
+            (TextBaseline::Alphabetic, 30 - GLYPH_ASCENT),
+            (TextBaseline::Bottom, 30 - 20),
+        ];
+
+        for &(baseline, top) in expected.iter() {
+            let mut canvas = new_canvas();
+            canvas.set_text_baseline(baseline);
+            canvas.draw_text(10, 30, "M\nM");
+            assert_eq!(top_row(&canvas), Some(top + inset), "{:?}", baseline);
+        }
+    }
+
+    #[test]
+    fn unknown_characters() {
+        let mut unknown = new_canvas();
+        unknown.draw_text(0, 0, "\u{263a}");
+        let mut question = new_canvas();
+        question.draw_text(0, 0, "?");
+
+        for y in 0..10 {
+            for x in 0..6 {
+                assert_eq!(unknown.get_point(x, y), question.get_point(x, y));
+            }
+        }
+        assert!(left_edge(&question, 0, 10).is_some());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::clip::Clip;
+use crate::{BlendMode, Canvas, FillRule, GraphicBuffer, Path, TextAlign, TextBaseline};
+
+/// An affine transformation, which maps a point (x, y) to:
+///
+/// (a*x + c*y + e, b*x + d*y + f)
+///
+/// This is the same layout that HTML canvas and SVG use.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    /// Creates a transform from its matrix entries
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Transform {
+        Transform { a, b, c, d, e, f }
+    }
+
+    /// Returns the transform that leaves every point where it is
+    pub fn identity() -> Transform {
+        Transform::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    /// Returns a transform that moves points by the given offsets
+    pub fn translation(x: f64, y: f64) -> Transform {
+        Transform::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    /// Returns a transform that turns points clockwise around the origin by
+    /// the given angle
+    pub fn rotation(angle: f64) -> Transform {
+        let (sin, cos) = angle.sin_cos();
+        Transform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Returns a transform that scales points away from the origin by the
+    /// given horizontal and vertical factors
+    pub fn scaling(x: f64, y: f64) -> Transform {
+        Transform::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Returns a transform that slants points horizontally and vertically by
+    /// the given angles
+    pub fn skewing(x: f64, y: f64) -> Transform {
+        Transform::new(1.0, y.tan(), x.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// Returns a transform that applies this transform and then the other one
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform::new(
+            other.a * self.a + other.c * self.b,
+            other.b * self.a + other.d * self.b,
+            other.a * self.c + other.c * self.d,
+            other.b * self.c + other.d * self.d,
+            other.a * self.e + other.c * self.f + other.e,
+            other.b * self.e + other.d * self.f + other.f,
+        )
+    }
+
+    /// Returns the transform that undoes this one, or None if this transform
+    /// squashes everything onto a line or a point
+    pub fn inverse(&self) -> Option<Transform> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        let (a, b) = (self.d / determinant, -self.b / determinant);
+        let (c, d) = (-self.c / determinant, self.a / determinant);
+        Some(Transform::new(
+            a,
+            b,
+            c,
+            d,
+            -(a * self.e + c * self.f),
+            -(b * self.e + d * self.f),
+        ))
+    }
+
+    /// Checks whether this transform leaves every point where it is
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    /// Moves the point through the transform
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// Returns how much the transform scales lengths on average, which is
+    /// used to size the lines drawn with it
+    pub(crate) fn scale_factor(&self) -> f64 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+}
+
+/// The settings that save and restore keep track of
+pub(crate) struct CanvasState<Element> {
+    pub fill: Element,
+    pub stroke: Element,
+    pub line_width: f64,
+    pub fill_rule: FillRule,
+    pub blend_mode: BlendMode,
+    pub text_scale: i64,
+    pub text_align: TextAlign,
+    pub text_baseline: TextBaseline,
+    pub transform: Transform,
+    pub clip: Clip,
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Gets the transform applied to the coordinates of everything drawn
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Replaces the current transform
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Goes back to drawing with untransformed pixel coordinates
+    pub fn reset_transform(&mut self) {
+        self.transform = Transform::identity();
+    }
+
+    /// Applies the given transform to coordinates before the current one
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.transform = transform.then(&self.transform);
+    }
+
+    /// Moves the origin of later drawing by the given offsets
+    pub fn translate(&mut self, x: f64, y: f64) {
+        self.apply_transform(&Transform::translation(x, y));
+    }
+
+    /// Turns later drawing clockwise around the origin by the given angle
+    pub fn rotate(&mut self, angle: f64) {
+        self.apply_transform(&Transform::rotation(angle));
+    }
+
+    /// Scales later drawing away from the origin by the given horizontal and
+    /// vertical factors
+    pub fn scale(&mut self, x: f64, y: f64) {
+        self.apply_transform(&Transform::scaling(x, y));
+    }
+
+    /// Slants later drawing horizontally and vertically by the given angles
+    pub fn skew(&mut self, x: f64, y: f64) {
+        self.apply_transform(&Transform::skewing(x, y));
+    }
+
+    /// Remembers the current transform, clip region, colors, line width, fill
+    /// rule, blend mode and text settings, so that they can be brought back
+    /// later by restore
+    pub fn save(&mut self) {
+        self.saved.push(CanvasState {
+            fill: self.fill,
+            stroke: self.stroke,
+            line_width: self.line_width,
+            fill_rule: self.fill_rule,
+            blend_mode: self.blend_mode,
+            text_scale: self.text_scale,
+            text_align: self.text_align,
+            text_baseline: self.text_baseline,
+            transform: self.transform,
+            clip: self.clip.clone(),
+        });
+    }
+
+    /// Brings back the settings from the most recent save. Does nothing if
+    /// there aren't any saved settings left.
+    pub fn restore(&mut self) {
+        if let Some(state) = self.saved.pop() {
+            self.fill = state.fill;
+            self.stroke = state.stroke;
+            self.line_width = state.line_width;
+            self.fill_rule = state.fill_rule;
+            self.blend_mode = state.blend_mode;
+            self.text_scale = state.text_scale;
+            self.text_align = state.text_align;
+            self.text_baseline = state.text_baseline;
+            self.transform = state.transform;
+            self.clip = state.clip;
+        }
+    }
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Checks whether coordinates have to go through the current transform,
+    /// rather than being used as pixel positions directly
+    pub(crate) fn is_transformed(&self) -> bool {
+        !self.transform.is_identity()
+    }
+
+    /// Moves a point through the current transform and rounds it to the
+    /// nearest pixel
+    pub(crate) fn transform_point(&self, x: i64, y: i64) -> (i64, i64) {
+        if !self.is_transformed() {
+            return (x, y);
+        }
+
+        let (px, py) = self.transform.apply((x as f64, y as f64));
+        (px.round() as i64, py.round() as i64)
+    }
+
+    /*
+    The rest of these draw the basic shapes when there's a transform. In that
+    case the shapes are no longer made up of whole pixels, so they're built
+    as paths instead. Gradients are worked out from each pixel's position
+    before the transform, which keeps them fixed to the shape.
+     */
+
+    /// Fills a region like gfill_rect when there's a transform
+    pub(crate) fn transformed_fill_rect<F>(
+        &mut self,
+        x: i64,
+        y: i64,
+        width: i64,
+        height: i64,
+        gradient: F,
+    ) where
+        F: Fn(f64, f64) -> Element,
+    {
+        // The region covers the whole of its outer pixels, so its edges are
+        // half a pixel out from their centers
+        let (left, top) = (x as f64 - 0.5, y as f64 - 0.5);
+        let (right, bottom) = (left + width as f64, top + height as f64);
+
+        let mut path = Path::new();
+        path.move_to(left, top);
+        path.line_to(right, top);
+        path.line_to(right, bottom);
+        path.line_to(left, bottom);
+        path.close();
+
+        self.fill_path_with(&path, |px, py| {
+            let xratio = ((px - x as f64) / width as f64).clamp(0.0, 1.0);
+            let yratio = ((py - y as f64) / height as f64).clamp(0.0, 1.0);
+            gradient(xratio, yratio)
+        });
+    }
+
+    /// Draws a border like gstroke_rect when there's a transform
+    pub(crate) fn transformed_stroke_rect<F>(
+        &mut self,
+        x: i64,
+        y: i64,
+        width: i64,
+        height: i64,
+        gradient: F,
+    ) where
+        F: Fn(f64, f64) -> Element,
+    {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let (left, top) = (x as f64, y as f64);
+        let (right, bottom) = ((x + width - 1) as f64, (y + height - 1) as f64);
+
+        let mut path = Path::new();
+        path.move_to(left, top);
+        path.line_to(right, top);
+        path.line_to(right, bottom);
+        path.line_to(left, bottom);
+        path.close();
+
+        self.stroke_path_with(&path, self.line_width, |px, py, _| {
+            let xratio = ((px - left) / width as f64).clamp(0.0, 1.0);
+            let yratio = ((py - top) / height as f64).clamp(0.0, 1.0);
+            gradient(xratio, yratio)
+        });
+    }
+
+    /// Draws a line like gstroke_line when there's a transform
+    pub(crate) fn transformed_line<F>(
+        &mut self,
+        x: i64,
+        y: i64,
+        x2: i64,
+        y2: i64,
+        width: f64,
+        gradient: F,
+    ) where
+        F: Fn(f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.move_to(x as f64, y as f64);
+        path.line_to(x2 as f64, y2 as f64);
+        self.stroke_path_with(&path, width, |_, _, ratio| gradient(ratio));
+    }
+
+    /// Draws a circle's perimeter like gstroke_circle when there's a
+    /// transform
+    pub(crate) fn transformed_stroke_circle<F>(&mut self, x: i64, y: i64, r: i64, gradient: F)
+    where
+        F: Fn(f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.ellipse(x as f64, y as f64, r as f64, r as f64, 0.0);
+        self.stroke_path_with(&path, self.line_width, |px, py, _| {
+            gradient((py - y as f64).atan2(px - x as f64))
+        });
+    }
+
+    /// Fills a circle like gfill_circle when there's a transform
+    pub(crate) fn transformed_fill_circle<F>(&mut self, x: i64, y: i64, r: i64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        let mut path = Path::new();
+        path.ellipse(x as f64, y as f64, r as f64, r as f64, 0.0);
+        self.fill_path_with(&path, |px, py| {
+            let (relx, rely) = (px - x as f64, py - y as f64);
+            let distance = (relx * relx + rely * rely).sqrt() / r as f64;
+            gradient(rely.atan2(relx), distance.min(1.0))
+        });
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{Canvas, GraphicBuffer};
+
+/// Checks whether a pixel at the given signed distance from the middle of a
+/// stroke is covered by it. One side is open and the other closed, so that a
+/// stroke that's N pixels wide covers exactly N pixels when it runs along the
+/// pixel grid.
+pub(crate) fn within(distance: f64, half_width: f64) -> bool {
+    -half_width < distance && distance <= half_width
+}
+
+/// Narrows the span [lo, hi] down to the values of t where |a*t + b| <= c
+fn clip_span(span: &mut (f64, f64), a: f64, b: f64, c: f64) {
+    if a == 0.0 {
+        if b.abs() > c {
+            *span = (f64::INFINITY, f64::NEG_INFINITY);
+        }
+        return;
+    }
+
+    let (t1, t2) = ((-c - b) / a, (c - b) / a);
+    span.0 = span.0.max(t1.min(t2));
+    span.1 = span.1.min(t1.max(t2));
+}
+
+/// Returns the pixel centers that lie within the span, allowing for a little
+/// rounding error on the boundaries. Empty spans, including infinite ones,
+/// produce empty ranges.
+fn span_pixels(span: (f64, f64)) -> std::ops::RangeInclusive<i64> {
+    let lo = (span.0 - 1e-9).ceil() as i64;
+    let hi = (span.1 + 1e-9).floor() as i64;
+    lo..=hi
+}
+
+/// Visits every pixel within margin of a straight segment which is
+/// half_width wide on each side and cut off square at the endpoints. The
+/// visitor is given the pixel, the ratio along the segment, how far the pixel
+/// is past the nearest end (negative between the ends), and its signed
+/// distance from the center of the segment.
+pub(crate) fn segment_pixels<F>(
+    x: f64,
+    y: f64,
+    x2: f64,
+    y2: f64,
+    half_width: f64,
+    margin: f64,
+    mut visit: F,
+) where
+    F: FnMut(i64, i64, f64, f64, f64),
+{
+    /*
+    The stroke is a rectangle rotated to follow the segment. Measuring from
+    the midpoint (mx, my), with (dx, dy) the unit direction of the segment and
+    (-dy, dx) the unit normal, a point is inside when:
+
+    |(px - mx) * dx + (py - my) * dy| <= length / 2
+    |(py - my) * dx - (px - mx) * dy| <= half_width
+
+    On a single row py is fixed, so both of these are linear in px and each one
+    bounds px to an interval. Walking only that interval avoids scanning the
+    whole bounding box, which is mostly empty for diagonal lines.
+     */
+    let length = ((x2 - x).powi(2) + (y2 - y).powi(2)).sqrt();
+    if length == 0.0 {
+        return;
+    }
+
+    let (dx, dy) = ((x2 - x) / length, (y2 - y) / length);
+    let (mx, my) = ((x + x2) / 2.0, (y + y2) / 2.0);
+    let reach = length / 2.0 + half_width + margin;
+
+    let top = (my - reach).floor() as i64;
+    let bottom = (my + reach).ceil() as i64;
+    for py in top..=bottom {
+        let rely = py as f64 - my;
+
+        let mut span = (f64::NEG_INFINITY, f64::INFINITY);
+        clip_span(&mut span, dx, rely * dy - mx * dx, length / 2.0 + margin);
+        clip_span(&mut span, -dy, rely * dx + mx * dy, half_width + margin);
+
+        for px in span_pixels(span) {
+            let relx = px as f64 - mx;
+            let along = relx * dx + rely * dy;
+            let across = rely * dx - relx * dy;
+            let ratio = ((along + length / 2.0) / length).clamp(0.0, 1.0);
+            visit(px, py, ratio, along.abs() - length / 2.0, across);
+        }
+    }
+}
+
+/// Visits every pixel within margin of a ring which is half_width wide on
+/// each side of the circle's perimeter. The visitor is given the pixel, its
+/// angle around the center and its signed distance from the perimeter.
+pub(crate) fn ring_pixels<F>(x: f64, y: f64, r: f64, half_width: f64, margin: f64, mut visit: F)
+where
+    F: FnMut(i64, i64, f64, f64),
+{
+    let outer = r + half_width + margin;
+    let inner = r - half_width - margin;
+
+    let top = (y - outer).floor() as i64;
+    let bottom = (y + outer).ceil() as i64;
+    for py in top..=bottom {
+        let rely = py as f64 - y;
+        if rely.abs() > outer {
+            continue;
+        }
+
+        // Only the parts of the row between the two circles need to be
+        // checked, which are either one span or two if the row passes through
+        // the hole in the middle
+        let outer_reach = (outer * outer - rely * rely).sqrt();
+        let spans = if inner > 0.0 && rely.abs() < inner {
+            let inner_reach = (inner * inner - rely * rely).sqrt();
+            [
+                (x - outer_reach, x - inner_reach),
+                (x + inner_reach, x + outer_reach),
+            ]
+        } else {
+            [
+                (x - outer_reach, x + outer_reach),
+                (f64::INFINITY, f64::NEG_INFINITY),
+            ]
+        };
+
+        for span in spans {
+            for px in span_pixels(span) {
+                let relx = px as f64 - x;
+                let distance = (relx * relx + rely * rely).sqrt() - r;
+                visit(px, py, rely.atan2(relx), distance);
+            }
+        }
+    }
+}
+
+impl<Element: Copy, Buffer: GraphicBuffer<Element>> Canvas<Element, Buffer> {
+    /// Draws a straight line of the current line width between the two points
+    /// using the given gradient(ratio)
+    pub(crate) fn wide_line<F>(&mut self, x: f64, y: f64, x2: f64, y2: f64, gradient: F)
+    where
+        F: Fn(f64) -> Element,
+    {
+        let half_width = self.line_width / 2.0;
+        segment_pixels(x, y, x2, y2, half_width, 0.0, |px, py, ratio, _, across| {
+            if within(across, half_width) {
+                self.put_point(px, py, gradient(ratio));
+            }
+        });
+    }
+
+    /// Draws a border of the current line width, centered on the edges of the
+    /// given region, using the given gradient(xratio, yratio)
+    pub(crate) fn wide_rect<F>(&mut self, x: i64, y: i64, width: i64, height: i64, gradient: F)
+    where
+        F: Fn(f64, f64) -> Element,
+    {
+        /*
+        The edges of the region run through the centers of its outermost
+        pixels, from (x, y) to (right, bottom). The distance used is the largest
+        distance past any one of those edges, which is negative inside of the
+        region. Measuring that way makes the corners of the border square,
+        the same as if the edges were extended to meet.
+         */
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let half_width = self.line_width / 2.0;
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        let reach = half_width.ceil() as i64;
+
+        for py in (y - reach)..=(bottom + reach) {
+            let yratio = ((py - y) as f64 / height as f64).clamp(0.0, 1.0);
+            let ydistance = (y - py).max(py - bottom) as f64;
+
+            // Rows that pass through the middle of the region only touch the
+            // left and right sides
+            let (columns, far_side) = if ydistance <= -half_width && right - x > 2 * reach {
+                (
+                    (x - reach)..=(x + reach),
+                    Some((right - reach)..=(right + reach)),
+                )
+            } else {
+                ((x - reach)..=(right + reach), None)
+            };
+
+            for px in columns.chain(far_side.into_iter().flatten()) {
+                let distance = ydistance.max((x - px).max(px - right) as f64);
+                if within(distance, half_width) {
+                    let xratio = ((px - x) as f64 / width as f64).clamp(0.0, 1.0);
+                    self.put_point(px, py, gradient(xratio, yratio));
+                }
+            }
+        }
+    }
+
+    /// Draws a circle's perimeter of the current line width around the given
+    /// point using the given gradient(angle)
+    pub(crate) fn wide_circle<F>(&mut self, x: i64, y: i64, r: i64, gradient: F)
+    where
+        F: Fn(f64) -> Element,
+    {
+        let half_width = self.line_width / 2.0;
+        ring_pixels(
+            x as f64,
+            y as f64,
+            r as f64,
+            half_width,
+            0.0,
+            |px, py, angle, distance| {
+                if within(distance, half_width) {
+                    self.put_point(px, py, gradient(angle));
+                }
+            },
+        );
+    }
+}
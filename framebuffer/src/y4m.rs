@@ -0,0 +1,265 @@
+use crate::write_all;
+use crate::FrameBuffer;
+use std::io;
+
+/// The chroma subsampling used for the frames in a YUV4MPEG2 stream
+#[derive(Clone, Copy, PartialEq)]
+pub enum Chroma {
+    /// Full resolution Y, Cb and Cr planes
+    C444,
+    /// Cb and Cr are averaged over 2x2 blocks, centered between the luma
+    /// samples (JPEG/MPEG-1 siting)
+    C420,
+}
+
+/// Converts an RGB triple into BT.601 limited-range Y, Cb and Cr
+fn rgb_to_ycbcr(r: i32, g: i32, b: i32) -> (u8, u8, u8) {
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let cb = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let cr = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    (y as u8, cb as u8, cr as u8)
+}
+
+/// Writes FrameBuffers as a YUV4MPEG2 stream, which carries its own frame
+/// rate and aspect ratio so that encoders don't have to guess them
+pub struct Y4mWriter<W: io::Write> {
+    output: W,
+    fps: (u32, u32),
+    aspect: (u32, u32),
+    chroma: Chroma,
+    size: Option<(u32, u32)>,
+}
+
+impl<W: io::Write> Y4mWriter<W> {
+    /// Creates a writer that emits frames at fps_num/fps_den frames per second
+    /// with square pixels
+    pub fn new(output: W, fps_num: u32, fps_den: u32, chroma: Chroma) -> Y4mWriter<W> {
+        Y4mWriter {
+            output,
+            fps: (fps_num, fps_den),
+            aspect: (1, 1),
+            chroma,
+            size: None,
+        }
+    }
+
+    /// Sets the pixel aspect ratio. This must be done before the first frame
+    /// is written, since it's part of the stream header.
+    pub fn set_aspect(&mut self, num: u32, den: u32) {
+        self.aspect = (num, den);
+    }
+
+    /// Flushes any frames that are still buffered in the output
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    /// Writes a single frame to the stream. The stream header is written along
+    /// with the first frame, and every later frame must have the same size.
+    pub fn write_frame(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        match self.size {
+            None => {
+                let chroma = match self.chroma {
+                    Chroma::C444 => "444",
+                    Chroma::C420 => "420jpeg",
+                };
+
+                let header = format!(
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A{}:{} C{}\n",
+                    frame.width,
+                    frame.height,
+                    self.fps.0,
+                    self.fps.1,
+                    self.aspect.0,
+                    self.aspect.1,
+                    chroma
+                );
+                write_all(&mut self.output, header.as_bytes())?;
+                self.size = Some((frame.width, frame.height));
+            }
+            Some((width, height)) => {
+                if width != frame.width || height != frame.height {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "frame size differs from the Y4M stream header",
+                    ));
+                }
+            }
+        }
+
+        write_all(&mut self.output, b"FRAME\n")?;
+        match self.chroma {
+            Chroma::C444 => self.write_444(frame),
+            Chroma::C420 => self.write_420(frame),
+        }
+    }
+
+    fn write_444(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        let size = (frame.width * frame.height) as usize;
+        let mut planes = vec![0; size * 3];
+
+        for (i, pixel) in frame.pixels.chunks(3).enumerate() {
+            let (y, cb, cr) = rgb_to_ycbcr(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            planes[i] = y;
+            planes[size + i] = cb;
+            planes[size * 2 + i] = cr;
+        }
+
+        write_all(&mut self.output, &planes)
+    }
+
+    fn write_420(&mut self, frame: &FrameBuffer) -> io::Result<()> {
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        let chroma_width = width.div_ceil(2);
+        let chroma_height = height.div_ceil(2);
+        let size = width * height;
+        let chroma_size = chroma_width * chroma_height;
+
+        let mut planes = vec![0; size + chroma_size * 2];
+        for (i, pixel) in frame.pixels.chunks(3).enumerate() {
+            let (y, _, _) = rgb_to_ycbcr(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            planes[i] = y;
+        }
+
+        // Average the RGB values for each 2x2 block before converting, which
+        // gives the same result as averaging Cb and Cr since the conversion
+        // is linear. Blocks on the right and bottom edges may be partial.
+        for cy in 0..chroma_height {
+            for cx in 0..chroma_width {
+                let (mut r, mut g, mut b, mut count) = (0, 0, 0, 0);
+                for py in (cy * 2)..(cy * 2 + 2).min(height) {
+                    for px in (cx * 2)..(cx * 2 + 2).min(width) {
+                        let offset = (py * width + px) * 3;
+                        r += frame.pixels[offset] as i32;
+                        g += frame.pixels[offset + 1] as i32;
+                        b += frame.pixels[offset + 2] as i32;
+                        count += 1;
+                    }
+                }
+
+                let round = count / 2;
+                let (_, cb, cr) = rgb_to_ycbcr(
+                    (r + round) / count,
+                    (g + round) / count,
+                    (b + round) / count,
+                );
+
+                let offset = cy * chroma_width + cx;
+                planes[size + offset] = cb;
+                planes[size + chroma_size + offset] = cr;
+            }
+        }
+
+        write_all(&mut self.output, &planes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, GraphicBuffer};
+
+    /// Builds a frame out of rows of colors
+    fn frame(rows: &[&[Color]]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                frame.put_point(x as i64, y as i64, color);
+            }
+        }
+        frame
+    }
+
+    /// Writes the frames and splits the output into the header fields and
+    /// what comes after the header
+    fn write(fps: (u32, u32), chroma: Chroma, frames: &[FrameBuffer]) -> (Vec<String>, Vec<u8>) {
+        let mut output = Vec::new();
+        let mut writer = Y4mWriter::new(&mut output, fps.0, fps.1, chroma);
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+
+        let end = output.iter().position(|&byte| byte == b'\n').unwrap();
+        let header = String::from_utf8(output[..end].to_vec()).unwrap();
+        let fields = header.split(' ').map(String::from).collect();
+        (fields, output[end + 1..].to_vec())
+    }
+
+    #[test]
+    fn rgb_to_ycbcr_known_values() {
+        assert_eq!(rgb_to_ycbcr(0, 0, 0), (16, 128, 128));
+        assert_eq!(rgb_to_ycbcr(255, 255, 255), (235, 128, 128));
+        assert_eq!(rgb_to_ycbcr(255, 0, 0), (82, 90, 240));
+        assert_eq!(rgb_to_ycbcr(0, 255, 0), (144, 54, 34));
+        assert_eq!(rgb_to_ycbcr(0, 0, 255), (41, 240, 110));
+    }
+
+    #[test]
+    fn header_fields() {
+        let black = Color::black();
+        let row: &[Color] = &[black; 5];
+        let (fields, _) = write((30000, 1001), Chroma::C420, &[frame(&[row; 3])]);
+        assert_eq!(
+            fields,
+            [
+                "YUV4MPEG2",
+                "W5",
+                "H3",
+                "F30000:1001",
+                "Ip",
+                "A1:1",
+                "C420jpeg"
+            ]
+        );
+
+        let mut output = Vec::new();
+        let mut writer = Y4mWriter::new(&mut output, 25, 1, Chroma::C444);
+        writer.set_aspect(4, 3);
+        writer.write_frame(&frame(&[&[black; 2]])).unwrap();
+        assert!(output.starts_with(b"YUV4MPEG2 W2 H1 F25:1 Ip A4:3 C444\nFRAME\n"));
+    }
+
+    #[test]
+    fn pixels_444() {
+        let red = Color::rgb(255, 0, 0);
+        let green = Color::rgb(0, 255, 0);
+        let (_, body) = write((30, 1), Chroma::C444, &[frame(&[&[red, green]])]);
+        assert_eq!(body[..6], *b"FRAME\n");
+        assert_eq!(body[6..], [82, 144, 90, 54, 240, 34]);
+    }
+
+    #[test]
+    fn pixels_420() {
+        // A red 2x2 block next to a white column, which gets a block of its
+        // own on the right edge
+        let red = Color::rgb(255, 0, 0);
+        let white = Color::white();
+        let rows: [&[Color]; 2] = [&[red, red, white], &[red, red, white]];
+        let (_, body) = write((30, 1), Chroma::C420, &[frame(&rows)]);
+        assert_eq!(body[..6], *b"FRAME\n");
+        assert_eq!(body[6..], [82, 82, 235, 82, 82, 235, 90, 128, 240, 128]);
+
+        // A block of red and green is averaged before converting
+        let green = Color::rgb(0, 255, 0);
+        let rows: [&[Color]; 2] = [&[red, green], &[green, red]];
+        let (_, body) = write((30, 1), Chroma::C420, &[frame(&rows)]);
+        let (_, cb, cr) = rgb_to_ycbcr(128, 128, 0);
+        assert_eq!(body[6..], [82, 144, 144, 82, cb, cr]);
+    }
+
+    #[test]
+    fn later_frames() {
+        let black = Color::black();
+        let white = Color::white();
+        let frames = [frame(&[&[black]]), frame(&[&[white]])];
+        let (fields, body) = write((30, 1), Chroma::C444, &frames);
+        assert_eq!(fields[1..3], ["W1", "H1"]);
+        assert_eq!(body, b"FRAME\n\x10\x80\x80FRAME\n\xeb\x80\x80");
+
+        let mut writer = Y4mWriter::new(Vec::new(), 30, 1, Chroma::C444);
+        writer.write_frame(&frame(&[&[black]])).unwrap();
+        let error = writer.write_frame(&frame(&[&[black, black]])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
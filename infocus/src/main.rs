@@ -91,6 +91,8 @@ fn random_color<T: random::Source>(source: &mut T) -> Color {
 
 fn main() {
     let mut rng = new_rng();
+    let mut sink = sink_from_args().unwrap();
+    let frames = frame_limit_from_args().unwrap().unwrap_or(u64::MAX);
 
     let mut lenses = Vec::new();
     for x in 0..(CANVAS_SIZE / (LENS_RADIUS * 2)) {
@@ -110,7 +112,7 @@ fn main() {
     let maskbuffer = StencilBuffer::new(CANVAS_SIZE as u32, CANVAS_SIZE as u32);
     let mut maskgfx = Canvas::new(maskbuffer, 0, 1);
 
-    loop {
+    for _ in 0..frames {
         maskgfx.fill();
 
         for x in 0..CANVAS_SIZE {
@@ -143,6 +145,8 @@ fn main() {
             _ => color,
         });
 
-        framegfx.buffer().write(&mut std::io::stdout()).unwrap();
+        sink.write_frame(framegfx.buffer()).unwrap();
     }
+
+    sink.finish().unwrap();
 }
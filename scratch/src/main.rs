@@ -1,18 +1,18 @@
 use framebuffer::*;
-use std::io;
 
 fn main() {
     let red = Color::rgb(255, 0, 0);
     let green = Color::rgb(0, 255, 0);
     let blue = Color::rgb(0, 0, 255);
 
-    let mut stdout = io::stdout();
+    let mut sink = sink_from_args().unwrap();
+    let frames = frame_limit_from_args().unwrap().unwrap_or(15 * 30);
 
     let buffer = FrameBuffer::new(400, 400);
     let mut gfx = Canvas::new(buffer, Color::white(), Color::black());
 
     let (mut a, mut b, mut c) = (red, green, blue);
-    for _i in 0..(15 * 30) {
+    for _i in 0..frames {
         for r in 1..80 {
             gfx.set_stroke(a);
             gfx.stroke_circle(200, 200, r * 3);
@@ -22,11 +22,13 @@ fn main() {
             gfx.stroke_circle(200, 200, (r * 3) + 2);
         }
 
-        gfx.buffer().write(&mut stdout).unwrap();
+        sink.write_frame(gfx.buffer()).unwrap();
 
         let tmp = a;
         a = b;
         b = c;
         c = tmp;
     }
+
+    sink.finish().unwrap();
 }
@@ -2,7 +2,6 @@ use framebuffer::*;
 use random;
 use random::Source;
 use std::f64;
-use std::io;
 use std::time::{Duration, SystemTime};
 
 /// Generates a seeded "random" value using the current process ID and time.
@@ -35,12 +34,17 @@ const ACCUMULATOR_COOL: u8 = 1;
 const ACCUMULATOR_MIN_HEAT: u8 = 50;
 
 fn main() {
-    let mut stdout = io::stdout();
+    let mut sink = sink_from_args().unwrap();
+    let frames = frame_limit_from_args().unwrap().unwrap_or(u64::MAX);
     let background = Color::black();
     let blur = Color::rgba(0, 0, 0, 15);
     let bolt = Color::white();
     let fill = Color::rgba(255, 255, 0, 120);
     let fill_halo = Color::rgba(255, 0, 0, 200);
+    let colormap = Colormap::from_stops(
+        &[(0.0, Color::black()), (1.0, Color::rgb(0, 255, 255))],
+        ColorSpace::Srgb,
+    );
     let buffer = FrameBuffer::new((CENTER_X * 2) as u32, (CENTER_Y * 2) as u32);
     let mut gfx = Canvas::new(buffer, background, Color::white());
     let mut rng = new_rng();
@@ -63,7 +67,7 @@ fn main() {
         accumulator_base_angles[i] = (i as f64) * (2.0 * f64::consts::PI) / (ACCUMULATOR_COUNT as f64);
     }
 
-    loop {
+    for _ in 0..frames {
         gfx.set_fill(blur);
         gfx.fill();
 
@@ -90,13 +94,18 @@ fn main() {
             let y = (ACCUMULATOR_RADIUS * angle.sin()) as i64 + CENTER_Y;
 
             if bleeding && i == target {
-                let offset = ACCUMULATOR_SIZE / 2;
+                let (cx, cy) = (CENTER_X as f64, CENTER_Y as f64);
+                let (x, y) = (x as f64, y as f64);
+                let offset = (ACCUMULATOR_SIZE / 2) as f64;
+                gfx.save();
+                gfx.set_blend_mode(BlendMode::Additive);
                 gfx.set_stroke(bolt);
-                gfx.stroke_line(CENTER_X, CENTER_Y, x, y);
-                gfx.stroke_line(CENTER_X, CENTER_Y, x - offset, y - offset);
-                gfx.stroke_line(CENTER_X, CENTER_Y, x + offset, y - offset);
-                gfx.stroke_line(CENTER_X, CENTER_Y, x - offset, y + offset);
-                gfx.stroke_line(CENTER_X, CENTER_Y, x + offset, y + offset);
+                gfx.aa_stroke_line(cx, cy, x, y);
+                gfx.aa_stroke_line(cx, cy, x - offset, y - offset);
+                gfx.aa_stroke_line(cx, cy, x + offset, y - offset);
+                gfx.aa_stroke_line(cx, cy, x - offset, y + offset);
+                gfx.aa_stroke_line(cx, cy, x + offset, y + offset);
+                gfx.restore();
 
                 if 255 - accumulator_heat[i] >= ACCUMULATOR_HEAT {
                     accumulator_heat[i] += ACCUMULATOR_HEAT;
@@ -109,19 +118,23 @@ fn main() {
                 accumulator_heat[i] = 0;
             }
 
-            let color = Color::rgb(0, accumulator_heat[i], accumulator_heat[i]);
+            let color = colormap.sample(accumulator_heat[i] as f64 / 255.0);
             gfx.set_fill(color);
-            gfx.fill_circle(x, y, ACCUMULATOR_SIZE);
+            gfx.aa_fill_circle(x as f64, y as f64, ACCUMULATOR_SIZE as f64);
         }
 
         // Draw the core
         gfx.set_fill(fill);
-        gfx.gfill_circle(CENTER_X, CENTER_Y, radius + jitter, |_, radius| {
+        let (cx, cy) = (CENTER_X as f64, CENTER_Y as f64);
+        gfx.aa_gfill_circle(cx, cy, (radius + jitter) as f64, |_, radius| {
             let mix = Color::rgba(0, 0, 0, 200 - (50.0 * radius) as u8);
             fill.blend(mix)
         });
 
-        // Draw the top-reaching bolts over the core
+        // Draw the top-reaching bolts over the core, adding their light to
+        // whatever is underneath
+        gfx.save();
+        gfx.set_blend_mode(BlendMode::Additive);
         for i in 0..ACCUMULATOR_COUNT {
             if (bleeding && target == i) || accumulator_heat[i] < ACCUMULATOR_MIN_HEAT {
                 continue;
@@ -131,24 +144,27 @@ fn main() {
             let x = (ACCUMULATOR_RADIUS * angle.cos()) as i64 + CENTER_X;
             let y = (ACCUMULATOR_RADIUS * angle.sin()) as i64 + CENTER_Y;
 
-            let color = Color::rgb(0, accumulator_heat[i], accumulator_heat[i]);
-            let offset = ACCUMULATOR_SIZE / 2;
+            let color = colormap.sample(accumulator_heat[i] as f64 / 255.0);
+            let (tx, ty) = (CENTER_X as f64, -100.0);
+            let (x, y) = (x as f64, y as f64);
+            let offset = (ACCUMULATOR_SIZE / 2) as f64;
             gfx.set_stroke(color);
-            gfx.stroke_line(x, y, CENTER_X, -100);
-            gfx.stroke_line(x - offset, y - offset, CENTER_X, -100);
-            gfx.stroke_line(x + offset, y - offset, CENTER_X, -100);
-            gfx.stroke_line(x - offset, y + offset, CENTER_X, -100);
-            gfx.stroke_line(x + offset, y + offset, CENTER_X, -100);
+            gfx.aa_stroke_line(x, y, tx, ty);
+            gfx.aa_stroke_line(x - offset, y - offset, tx, ty);
+            gfx.aa_stroke_line(x + offset, y - offset, tx, ty);
+            gfx.aa_stroke_line(x - offset, y + offset, tx, ty);
+            gfx.aa_stroke_line(x + offset, y + offset, tx, ty);
         }
+        gfx.restore();
 
         // Post-fill the halo so it affects all the energy bolts, including a
         // shading gradient that gets darker toward the center of the core
-        gfx.gfill_circle(CENTER_X, CENTER_Y, radius, |_, radius| {
+        gfx.aa_gfill_circle(cx, cy, radius as f64, |_, radius| {
             let mix = Color::rgba(0, 0, 0, (200.0 * radius.max(0.25)) as u8);
             fill_halo.blend(mix)
         });
 
-        gfx.buffer().write(&mut stdout).unwrap();
+        sink.write_frame(gfx.buffer()).unwrap();
 
         if bleeding && energy == CORE_MIN_ENERGY {
             bleeding = false;
@@ -157,4 +173,6 @@ fn main() {
             bleeding = true;
         }
     }
+
+    sink.finish().unwrap();
 }